@@ -0,0 +1,39 @@
+use core::arch::asm;
+
+const COM1: u16 = 0x3f8;
+const COM1_LINE_STATUS: u16 = COM1 + 5;
+const TRANSMITTER_HOLDING_EMPTY: u8 = 1 << 5;
+
+// upper bound on how long we wait for the uart before giving up on a byte
+const SPIN_LIMIT: usize = 100_000;
+
+/*
+ * Writes `s` straight to the COM1 data port, one byte at a time.
+ * This does not go through any lazy initialization or lock, so it can be used in the earliest code
+ * (right after the asm to Rust transition) or when the `WRITER` lock might already be held.
+ *
+ * It bypasses any serial port initialization, meaning that the port is used with whatever configuration
+ * the firmware/bootloader left it in. If the port isn't configured, the output may be garbled (or lost).
+ */
+pub fn early_print(s: &str) {
+    for byte in s.bytes() {
+        // wait (bounded) for the transmitter to be ready so we never hang here
+        for _ in 0..SPIN_LIMIT {
+            if unsafe { inb(COM1_LINE_STATUS) } & TRANSMITTER_HOLDING_EMPTY != 0 {
+                break;
+            }
+        }
+
+        unsafe { outb(COM1, byte) };
+    }
+}
+
+unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn outb(port: u16, value: u8) {
+    asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
@@ -0,0 +1,32 @@
+use core::{arch::asm, sync::atomic::{self, Ordering}};
+
+/*
+ * Prevents the compiler from reordering memory accesses across this point (no instruction is emitted).
+ * Needed when memory is accessed through two different virtual addresses that the compiler can't know alias,
+ * e.g. writing a page table entry and then touching the table it points to through the recursive mapping.
+ */
+#[inline(always)]
+pub fn compiler_fence() {
+    atomic::compiler_fence(Ordering::SeqCst);
+}
+
+/*
+ * Full hardware memory fence: every load and store before it is globally visible before any load or store after it.
+ * x86 already keeps stores ordered with each other for normal (write back) memory, so this is mostly
+ * needed for MMIO registers mapped as write combining and for non temporal stores.
+ */
+#[inline(always)]
+pub fn mfence() {
+    unsafe { asm!("mfence", options(nostack, preserves_flags)) };
+}
+
+/*
+ * Writes back all modified cache lines to memory and invalidates the caches.
+ * This is very slow and only makes sense when changing the caching type of memory (e.g. MTRRs/PAT).
+ *
+ * Safety: This is a privileged instruction, it must only be called from ring 0.
+ */
+#[inline(always)]
+pub unsafe fn wbinvd() {
+    asm!("wbinvd", options(nostack, preserves_flags));
+}
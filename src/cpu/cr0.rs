@@ -0,0 +1,45 @@
+use bitflags::bitflags;
+use core::arch::asm;
+
+bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    pub struct Cr0: u64 {
+        const PROTECTED_MODE_ENABLE = 1 << 0;
+        const MONITOR_COPROCESSOR   = 1 << 1;
+        const EMULATE_COPROCESSOR   = 1 << 2;  // x87 instructions cause a #NM
+        const TASK_SWITCHED         = 1 << 3;
+        const EXTENSION_TYPE        = 1 << 4;  // read only in long mode, always 1
+        const NUMERIC_ERROR         = 1 << 5;
+        const WRITE_PROTECT         = 1 << 16; // ring 0 writes also respect read only pages
+        const ALIGNMENT_MASK        = 1 << 18;
+        const NOT_WRITE_THROUGH     = 1 << 29;
+        const CACHE_DISABLE         = 1 << 30;
+        const PAGING                = 1 << 31; // set by boot.asm
+    }
+}
+
+pub fn get() -> Cr0 {
+    let value: u64;
+    unsafe { asm!("mov {}, cr0", out(reg) value, options(nomem, nostack, preserves_flags)) };
+    Cr0::from_bits_retain(value)
+}
+
+/*
+ * Safety: The caller must make sure that the new state is valid for the running kernel (clearing paging or
+ * protected mode, for example, is fatal).
+ */
+pub unsafe fn set(flags: Cr0) {
+    asm!("mov cr0, {}", in(reg) flags.bits(), options(nostack, preserves_flags));
+}
+
+/*
+ * With WRITE_PROTECT cleared, the kernel can write to pages that are not WRITABLE.
+ * This is meant for short, controlled patches and should be turned back on right after.
+ *
+ * Safety: Any read only data written to while it is cleared must be safe to modify.
+ */
+pub unsafe fn set_write_protect(enabled: bool) {
+    let mut flags = get();
+    flags.set(Cr0::WRITE_PROTECT, enabled);
+    set(flags);
+}
@@ -0,0 +1,50 @@
+use super::msr::{self, IA32_EFER};
+use bitflags::bitflags;
+
+bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    pub struct Efer: u64 {
+        const SYSCALL_ENABLE                 = 1 << 0;  // enables the syscall/sysret instructions
+        const LONG_MODE_ENABLE               = 1 << 8;  // set by boot.asm before enabling paging
+        const LONG_MODE_ACTIVE               = 1 << 10; // read only, set by the cpu when long mode is active
+        const NO_EXECUTE_ENABLE              = 1 << 11; // enables the NO_EXECUTE bit in page table entries
+        const SECURE_VIRTUAL_MACHINE_ENABLE  = 1 << 12;
+        const LONG_MODE_SEGMENT_LIMIT_ENABLE = 1 << 13;
+        const FAST_FXSAVE_FXRSTOR            = 1 << 14;
+        const TRANSLATION_CACHE_EXTENSION    = 1 << 15;
+    }
+}
+
+pub fn get() -> Efer {
+    // EFER always exists in long mode, and we are in long mode
+    Efer::from_bits_retain(unsafe { msr::read(IA32_EFER) })
+}
+
+/*
+ * Safety: The caller must make sure that the new state is valid for the running kernel (clearing long mode, for example, is fatal).
+ */
+pub unsafe fn set(flags: Efer) {
+    msr::write(IA32_EFER, flags.bits());
+}
+
+/*
+ * Changing this has immediate effect on every translation, including the ones already cached.
+ * With NXE cleared, the NO_EXECUTE bit becomes a reserved bit, so any present page mapped with it will cause
+ * a page fault (reserved bit violation) when accessed.
+ *
+ * Safety: See above, no page mapped with `EntryFlags::NO_EXECUTE` may be accessed after clearing it.
+ */
+pub unsafe fn set_nxe(enabled: bool) {
+    let mut flags = get();
+    flags.set(Efer::NO_EXECUTE_ENABLE, enabled);
+    set(flags);
+}
+
+/*
+ * Safety: `syscall` must not be used before the STAR/LSTAR MSRs are set up.
+ */
+pub unsafe fn set_sce(enabled: bool) {
+    let mut flags = get();
+    flags.set(Efer::SYSCALL_ENABLE, enabled);
+    set(flags);
+}
@@ -0,0 +1,4 @@
+pub mod barrier;
+pub mod msr;
+pub mod efer;
+pub mod cr0;
@@ -0,0 +1,23 @@
+// https://wiki.osdev.org/Model_Specific_Registers
+use core::arch::asm;
+
+pub const IA32_EFER: u32 = 0xc0000080;
+
+/*
+ * Safety: `msr` must be a valid MSR on this cpu, otherwise a general protection fault is raised.
+ */
+pub unsafe fn read(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    (high as u64) << 32 | low as u64
+}
+
+/*
+ * Safety: `msr` must be a valid MSR on this cpu and `value` must be valid for it.
+ * Writing MSRs can change the cpu behavior in ways that break memory safety (e.g. EFER or the FS/GS bases).
+ */
+pub unsafe fn write(msr: u32, value: u64) {
+    let low = value as u32;
+    let high = (value >> 32) as u32;
+    asm!("wrmsr", in("ecx") msr, in("eax") low, in("edx") high, options(nostack, preserves_flags));
+}
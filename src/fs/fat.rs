@@ -0,0 +1,729 @@
+// https://wiki.osdev.org/FAT
+// https://academy.cba.mit.edu/classes/networking_communications/SD/FAT.pdf
+use super::{DirEntryInfo, FileHandle, Vfs, VfsError};
+use crate::storage::{BlockError, BlockRead};
+use spin::Mutex;
+
+// the smallest and biggest sector sizes allowed by the spec
+const MIN_SECTOR_SIZE: usize = 512;
+const MAX_SECTOR_SIZE: usize = 4096;
+
+// clusters are numbered from 2 and every number from 0x0ffffff7 up has a special meaning in FAT32
+const MAX_CLUSTER_COUNT: u64 = 0x0ffffff5;
+
+const DIR_ENTRY_SIZE: usize = 32;
+const DIR_ENTRY_END: u8 = 0x00;
+const DIR_ENTRY_DELETED: u8 = 0xe5;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+#[derive(Debug)]
+pub enum FatError {
+    BlockErr(BlockError),
+    MissingBootSignature,
+    SectorSizeMismatch,
+    ScratchTooSmall,
+    InvalidBpb,
+    InvalidCluster(u32),
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+}
+
+impl From<BlockError> for FatError {
+    fn from(err: BlockError) -> Self {
+        FatError::BlockErr(err)
+    }
+}
+
+impl From<FatError> for VfsError {
+    fn from(err: FatError) -> Self {
+        match err {
+            FatError::NotFound => VfsError::NotFound,
+            FatError::NotADirectory => VfsError::NotADirectory,
+            FatError::IsADirectory => VfsError::IsADirectory,
+            _ => VfsError::BackendErr,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+// the last sector read from the device, every read goes through here so no sector sized buffers end up in the stack
+struct SectorCache<'a> {
+    buf: &'a mut [u8], // exactly `bytes_per_sector` long
+    sector: Option<u64>,
+}
+
+/*
+ * A read only FAT12/16/32 filesystem living on `device`, starting at `partition_start` (an lba).
+ * Only short (8.3) names are supported, long file name entries are skipped.
+ */
+pub struct FatFs<'a, D: BlockRead> {
+    device: &'a D,
+    partition_start: u64,
+    cache: Mutex<SectorCache<'a>>,
+    fat_type: FatType,
+
+    bytes_per_sector: usize,
+    sectors_per_cluster: u64,
+    cluster_count: u32,
+
+    // all of these are sector numbers relative to the start of the partition
+    fat_start: u64,
+    root_dir_start: u64, // only used in FAT12/16
+    root_dir_sectors: u64, // only used in FAT12/16
+    data_start: u64,
+
+    root_cluster: u32, // only used in FAT32
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DirEntry {
+    name: [u8; 12], // `NAME.EXT`, at most 12 bytes
+    name_len: usize,
+    attributes: u8,
+    first_cluster: u32,
+    size: u32,
+}
+
+impl DirEntry {
+    pub fn name(&self) -> &str {
+        // the name bytes are validated to be ascii when the entry is parsed
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+/*
+ * Position in a chain of sectors (either the fixed FAT12/16 root dir region or a cluster chain).
+ * `hops` is the amount of clusters followed so far, used to stop on cyclic chains.
+ */
+#[derive(Clone, Copy)]
+enum ChainPos {
+    RootRegion { sector: u64 },
+    Cluster { cluster: u32, sector: u64, hops: u32 },
+    End,
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+impl<'a, D: BlockRead> FatFs<'a, D> {
+    /*
+     * `scratch` is used to hold one sector at a time, so it must be at least as big as a device block.
+     * Keeping it outside of the stack matters as the kernel stack is small and has no guard page.
+     */
+    pub fn mount(device: &'a D, partition_start: u64, scratch: &'a mut [u8]) -> Result<Self, FatError> {
+        let block_size = device.block_size();
+        if !(MIN_SECTOR_SIZE..=MAX_SECTOR_SIZE).contains(&block_size) {
+            return Err(FatError::SectorSizeMismatch);
+        }
+
+        let boot_sector = scratch.get_mut(..block_size).ok_or(FatError::ScratchTooSmall)?;
+        device.read_block(partition_start, boot_sector)?;
+
+        if boot_sector[510] != 0x55 || boot_sector[511] != 0xaa {
+            return Err(FatError::MissingBootSignature);
+        }
+
+        // we read sectors directly as device blocks, so both sizes must match
+        let bytes_per_sector = read_u16(boot_sector, 11) as usize;
+        if bytes_per_sector != block_size {
+            return Err(FatError::SectorSizeMismatch);
+        }
+
+        let sectors_per_cluster = boot_sector[13] as u64;
+        let reserved_sectors = read_u16(boot_sector, 14) as u64;
+        let fat_count = boot_sector[16] as u64;
+        let root_entry_count = read_u16(boot_sector, 17) as u64;
+        let total_sectors = match read_u16(boot_sector, 19) {
+            0 => read_u32(boot_sector, 32) as u64,
+            n => n as u64,
+        };
+        let fat_size = match read_u16(boot_sector, 22) {
+            0 => read_u32(boot_sector, 36) as u64, // FAT32 extended BPB
+            n => n as u64,
+        };
+
+        if sectors_per_cluster == 0 || !sectors_per_cluster.is_power_of_two() || fat_count == 0 || fat_size == 0 {
+            return Err(FatError::InvalidBpb);
+        }
+
+        let root_dir_sectors = (root_entry_count * DIR_ENTRY_SIZE as u64).div_ceil(bytes_per_sector as u64);
+        let root_dir_start = reserved_sectors + fat_count * fat_size;
+        let data_start = root_dir_start + root_dir_sectors;
+        let data_sectors = total_sectors.checked_sub(data_start).ok_or(FatError::InvalidBpb)?;
+        let cluster_count = data_sectors / sectors_per_cluster;
+        if cluster_count > MAX_CLUSTER_COUNT {
+            return Err(FatError::InvalidBpb);
+        }
+
+        let cluster_count = cluster_count as u32;
+
+        // the FAT type is determined by the cluster count alone (see the spec)
+        let fat_type = match cluster_count {
+            0..4085 => FatType::Fat12,
+            4085..65525 => FatType::Fat16,
+            _ => FatType::Fat32,
+        };
+
+        let root_cluster = read_u32(boot_sector, 44);
+        let fs = FatFs {
+            device,
+            partition_start,
+            cache: Mutex::new(SectorCache { buf: boot_sector, sector: Some(0) }),
+            fat_type,
+            bytes_per_sector,
+            sectors_per_cluster,
+            cluster_count,
+            fat_start: reserved_sectors,
+            root_dir_start,
+            root_dir_sectors,
+            data_start,
+            root_cluster,
+        };
+
+        if fat_type == FatType::Fat32 {
+            fs.check_cluster(fs.root_cluster)?;
+        }
+
+        Ok(fs)
+    }
+
+    pub fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+
+    fn check_cluster(&self, cluster: u32) -> Result<(), FatError> {
+        // the first 2 clusters are reserved
+        if cluster < 2 || cluster >= self.cluster_count + 2 {
+            return Err(FatError::InvalidCluster(cluster));
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Runs `f` with the contents of `sector`, reading it from the device unless it is the cached one.
+     * `f` must not call back into the filesystem, the cache is locked while it runs.
+     */
+    fn with_sector<R>(&self, sector: u64, f: impl FnOnce(&[u8]) -> R) -> Result<R, FatError> {
+        let mut cache = self.cache.lock();
+        if cache.sector != Some(sector) {
+            // a failed read may leave the buffer half written
+            cache.sector = None;
+            self.device.read_block(self.partition_start + sector, cache.buf)?;
+            cache.sector = Some(sector);
+        }
+
+        Ok(f(cache.buf))
+    }
+
+    // returns the next cluster in the chain or None if `cluster` is the last one
+    fn next_cluster(&self, cluster: u32) -> Result<Option<u32>, FatError> {
+        let (offset, len) = match self.fat_type {
+            FatType::Fat12 => (cluster as usize + cluster as usize / 2, 2),
+            FatType::Fat16 => (cluster as usize * 2, 2),
+            FatType::Fat32 => (cluster as usize * 4, 4),
+        };
+
+        // FAT12 entries may cross a sector boundary so read the entry byte by byte
+        let mut value = 0u32;
+        for i in 0..len {
+            let sector = ((offset + i) / self.bytes_per_sector) as u64;
+            let byte = self.with_sector(self.fat_start + sector, |buf| buf[(offset + i) % self.bytes_per_sector])?;
+            value |= (byte as u32) << (i * 8);
+        }
+
+        let (next, end_of_chain) = match self.fat_type {
+            FatType::Fat12 => (if cluster & 1 == 1 { value >> 4 } else { value & 0xfff }, 0xff8),
+            FatType::Fat16 => (value, 0xfff8),
+            FatType::Fat32 => (value & 0x0fffffff, 0x0ffffff8),
+        };
+
+        if next >= end_of_chain {
+            return Ok(None);
+        }
+
+        // this also catches free (0) and bad (0x...ff7) clusters in the middle of a chain
+        self.check_cluster(next)?;
+        Ok(Some(next))
+    }
+
+    // a first cluster of 0 means the root directory
+    fn chain_start(&self, first_cluster: u32) -> Result<ChainPos, FatError> {
+        if first_cluster == 0 && self.fat_type != FatType::Fat32 {
+            return Ok(ChainPos::RootRegion { sector: 0 });
+        }
+
+        let cluster = if first_cluster == 0 { self.root_cluster } else { first_cluster };
+        self.check_cluster(cluster)?;
+        Ok(ChainPos::Cluster { cluster, sector: 0, hops: 0 })
+    }
+
+    fn chain_advance(&self, pos: ChainPos) -> Result<ChainPos, FatError> {
+        Ok(match pos {
+            ChainPos::RootRegion { sector } if sector + 1 < self.root_dir_sectors => ChainPos::RootRegion { sector: sector + 1 },
+            ChainPos::RootRegion { .. } => ChainPos::End,
+            ChainPos::Cluster { cluster, sector, hops } if sector + 1 < self.sectors_per_cluster => ChainPos::Cluster { cluster, sector: sector + 1, hops },
+            ChainPos::Cluster { cluster, hops, .. } => match self.next_cluster(cluster)? {
+                // a chain can't be longer than the amount of clusters, so it must be looping back on itself
+                Some(next) if hops + 1 >= self.cluster_count => return Err(FatError::InvalidCluster(next)),
+                Some(next) => ChainPos::Cluster { cluster: next, sector: 0, hops: hops + 1 },
+                None => ChainPos::End,
+            },
+            ChainPos::End => ChainPos::End,
+        })
+    }
+
+    fn chain_sector(&self, pos: ChainPos) -> Option<u64> {
+        match pos {
+            ChainPos::RootRegion { sector } if sector < self.root_dir_sectors => Some(self.root_dir_start + sector),
+            ChainPos::RootRegion { .. } => None,
+            ChainPos::Cluster { cluster, sector, .. } => Some(self.data_start + (cluster as u64 - 2) * self.sectors_per_cluster + sector),
+            ChainPos::End => None,
+        }
+    }
+
+    pub fn root_dir(&self) -> Result<DirIter<'_, 'a, D>, FatError> {
+        self.read_dir_at(0)
+    }
+
+    pub fn read_dir(&self, dir: &DirEntry) -> Result<DirIter<'_, 'a, D>, FatError> {
+        if !dir.is_dir() {
+            return Err(FatError::NotADirectory);
+        }
+
+        self.read_dir_at(dir.first_cluster)
+    }
+
+    fn read_dir_at(&self, first_cluster: u32) -> Result<DirIter<'_, 'a, D>, FatError> {
+        Ok(DirIter {
+            fs: self,
+            pos: self.chain_start(first_cluster)?,
+            curr_entry_idx: 0,
+        })
+    }
+
+    /*
+     * Finds the entry at `path` (components separated by '/', case insensitive, 8.3 names only).
+     * Returns `FatError::NotFound` for an empty path as the root directory has no entry of its own.
+     */
+    pub fn find(&self, path: &str) -> Result<DirEntry, FatError> {
+        let mut found: Option<DirEntry> = None;
+
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            let mut dir = match &found {
+                None => self.root_dir()?,
+                Some(entry) => self.read_dir(entry)?,
+            };
+
+            found = Some(loop {
+                match dir.next() {
+                    Some(entry) => {
+                        let entry = entry?;
+                        if entry.name().eq_ignore_ascii_case(component) {
+                            break entry;
+                        }
+                    }
+                    None => return Err(FatError::NotFound),
+                }
+            });
+        }
+
+        found.ok_or(FatError::NotFound)
+    }
+
+    /*
+     * Reads the file contents starting at `offset` into `buf`.
+     * Returns the amount of bytes read, which is less than `buf.len()` if the end of the file is reached.
+     */
+    pub fn read_file(&self, file: &DirEntry, offset: usize, buf: &mut [u8]) -> Result<usize, FatError> {
+        if file.is_dir() {
+            return Err(FatError::IsADirectory);
+        }
+
+        let file_size = file.size as usize;
+        if offset >= file_size || file.first_cluster == 0 {
+            return Ok(0);
+        }
+
+        // skip the sectors before the offset
+        let mut pos = self.chain_start(file.first_cluster)?;
+        for _ in 0..offset / self.bytes_per_sector {
+            pos = self.chain_advance(pos)?;
+        }
+
+        let to_read = buf.len().min(file_size - offset);
+        let mut sector_offset = offset % self.bytes_per_sector;
+        let mut read = 0;
+
+        while read < to_read {
+            // the chain ending before the file size is a corrupted filesystem, return what we have
+            let Some(sector) = self.chain_sector(pos) else {
+                break;
+            };
+
+            let len = (self.bytes_per_sector - sector_offset).min(to_read - read);
+            let dst = &mut buf[read..read + len];
+            self.with_sector(sector, |sector_buf| dst.copy_from_slice(&sector_buf[sector_offset..sector_offset + len]))?;
+
+            read += len;
+            sector_offset = 0;
+            pos = self.chain_advance(pos)?;
+        }
+
+        Ok(read)
+    }
+
+    pub fn read_path(&self, path: &str, buf: &mut [u8]) -> Result<usize, FatError> {
+        let file = self.find(path)?;
+        self.read_file(&file, 0, buf)
+    }
+}
+
+// the inode of a file is its first cluster (0 being the root directory)
+impl<'a, D: BlockRead> Vfs for FatFs<'a, D> {
+    fn open(&self, path: &str) -> Result<FileHandle, VfsError> {
+        if path.is_empty() {
+            return Ok(FileHandle { mount: 0, inode: 0, size: 0, is_dir: true });
+        }
+
+        let entry = self.find(path)?;
+        Ok(FileHandle {
+            mount: 0,
+            inode: entry.first_cluster as u64,
+            size: entry.size as u64,
+            is_dir: entry.is_dir(),
+        })
+    }
+
+    fn read(&self, file: &FileHandle, offset: usize, buf: &mut [u8]) -> Result<usize, VfsError> {
+        let entry = DirEntry {
+            name: [0; 12],
+            name_len: 0,
+            attributes: if file.is_dir { ATTR_DIRECTORY } else { 0 },
+            first_cluster: file.inode as u32,
+            size: file.size as u32,
+        };
+
+        Ok(self.read_file(&entry, offset, buf)?)
+    }
+
+    fn readdir(&self, path: &str, f: &mut dyn FnMut(DirEntryInfo)) -> Result<(), VfsError> {
+        let dir = match path.is_empty() {
+            true => self.root_dir()?,
+            false => self.read_dir(&self.find(path)?)?,
+        };
+
+        for entry in dir {
+            let entry = entry?;
+            if entry.name() == "." || entry.name() == ".." {
+                continue;
+            }
+
+            f(DirEntryInfo { name: entry.name(), is_dir: entry.is_dir(), size: entry.size as u64 });
+        }
+
+        Ok(())
+    }
+}
+
+/*
+ * Iterates the entries in a directory, skipping deleted, volume id and long file name entries.
+ * The `.` and `..` entries are returned like any other.
+ */
+pub struct DirIter<'fs, 'a, D: BlockRead> {
+    fs: &'fs FatFs<'a, D>,
+    pos: ChainPos,
+    curr_entry_idx: usize, // index of the entry in the current sector
+}
+
+impl<'fs, 'a, D: BlockRead> DirIter<'fs, 'a, D> {
+    fn next_raw(&mut self) -> Result<Option<DirEntry>, FatError> {
+        let bytes_per_sector = self.fs.bytes_per_sector;
+
+        loop {
+            if self.curr_entry_idx * DIR_ENTRY_SIZE >= bytes_per_sector {
+                self.pos = self.fs.chain_advance(self.pos)?;
+                self.curr_entry_idx = 0;
+            }
+
+            let Some(sector) = self.fs.chain_sector(self.pos) else {
+                return Ok(None);
+            };
+
+            // only the entry itself is copied out of the (shared) sector cache
+            let offset = self.curr_entry_idx * DIR_ENTRY_SIZE;
+            let mut raw = [0u8; DIR_ENTRY_SIZE];
+            self.fs.with_sector(sector, |buf| raw.copy_from_slice(&buf[offset..offset + DIR_ENTRY_SIZE]))?;
+            self.curr_entry_idx += 1;
+
+            // an entry starting with 0 marks the end of the directory
+            if raw[0] == DIR_ENTRY_END {
+                self.pos = ChainPos::End;
+                return Ok(None);
+            }
+
+            let attributes = raw[11];
+            if raw[0] == DIR_ENTRY_DELETED || attributes & ATTR_LONG_NAME == ATTR_LONG_NAME || attributes & ATTR_VOLUME_ID != 0 {
+                continue;
+            }
+
+            return Ok(Some(parse_dir_entry(&raw)));
+        }
+    }
+}
+
+impl<'fs, 'a, D: BlockRead> Iterator for DirIter<'fs, 'a, D> {
+    type Item = Result<DirEntry, FatError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_raw() {
+            Ok(entry) => entry.map(Ok),
+            Err(err) => {
+                // don't keep going over a broken chain
+                self.pos = ChainPos::End;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// turns the space padded `NAME    EXT` into `NAME.EXT`
+fn parse_dir_entry(raw: &[u8]) -> DirEntry {
+    let mut name = [0u8; 12];
+    let mut name_len = 0;
+
+    let mut push = |bytes: &[u8]| {
+        for &byte in bytes.iter().take_while(|&&b| b != b' ') {
+            // anything that is not ascii (code page characters) is replaced
+            name[name_len] = if byte.is_ascii() { byte } else { b'?' };
+            name_len += 1;
+        }
+    };
+
+    push(&raw[0..8]);
+    if raw[8] != b' ' {
+        push(b".");
+        push(&raw[8..11]);
+    }
+
+    // 0x05 is used as an escape for a real 0xe5 first byte
+    if name[0] == 0x05 {
+        name[0] = b'?';
+    }
+
+    DirEntry {
+        name,
+        name_len,
+        attributes: raw[11],
+        first_cluster: (read_u16(raw, 20) as u32) << 16 | read_u16(raw, 26) as u32,
+        size: read_u32(raw, 28),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ramdisk::RamDisk;
+
+    /*
+     * A tiny FAT12 image: 512 byte sectors, 1 sector per cluster, 1 reserved sector, 2 FATs of 1 sector each,
+     * 16 root entries (1 sector) and 64 sectors in total, so the data (60 clusters) starts at sector 4.
+     *
+     *  /HELLO.TXT     600 bytes in clusters 2 -> 3
+     *  /SUB/          cluster 4
+     *  /SUB/INNER.BIN 10 bytes in cluster 5
+     */
+    const SECTOR: usize = 512;
+    const TOTAL_SECTORS: usize = 64;
+    const ROOT_DIR_SECTOR: usize = 3;
+    const DATA_SECTOR: usize = 4;
+    const HELLO_SIZE: usize = 600;
+
+    fn set_fat(img: &mut [u8], cluster: usize, value: u16) {
+        for fat in [1, 2] {
+            let offset = fat * SECTOR + cluster + cluster / 2;
+            let old = u16::from_le_bytes([img[offset], img[offset + 1]]);
+            let new = match cluster & 1 {
+                0 => (old & 0xf000) | value,
+                _ => (old & 0x000f) | (value << 4),
+            };
+            img[offset..offset + 2].copy_from_slice(&new.to_le_bytes());
+        }
+    }
+
+    fn put_entry(img: &mut [u8], sector: usize, idx: usize, name: &[u8; 11], attributes: u8, cluster: u16, size: u32) {
+        let entry = &mut img[sector * SECTOR + idx * DIR_ENTRY_SIZE..][..DIR_ENTRY_SIZE];
+        entry[0..11].copy_from_slice(name);
+        entry[11] = attributes;
+        entry[26..28].copy_from_slice(&cluster.to_le_bytes());
+        entry[28..32].copy_from_slice(&size.to_le_bytes());
+    }
+
+    fn cluster_sector(cluster: usize) -> usize {
+        DATA_SECTOR + cluster - 2
+    }
+
+    fn hello_byte(idx: usize) -> u8 {
+        (idx % 251) as u8
+    }
+
+    fn image() -> Vec<u8> {
+        let mut img = vec![0u8; TOTAL_SECTORS * SECTOR];
+
+        // BPB
+        img[11..13].copy_from_slice(&(SECTOR as u16).to_le_bytes());
+        img[13] = 1;
+        img[14..16].copy_from_slice(&1u16.to_le_bytes());
+        img[16] = 2;
+        img[17..19].copy_from_slice(&16u16.to_le_bytes());
+        img[19..21].copy_from_slice(&(TOTAL_SECTORS as u16).to_le_bytes());
+        img[22..24].copy_from_slice(&1u16.to_le_bytes());
+        img[510] = 0x55;
+        img[511] = 0xaa;
+
+        set_fat(&mut img, 0, 0xff8);
+        set_fat(&mut img, 1, 0xfff);
+        set_fat(&mut img, 2, 3);
+        set_fat(&mut img, 3, 0xfff);
+        set_fat(&mut img, 4, 0xfff);
+        set_fat(&mut img, 5, 0xfff);
+
+        // the volume label, the long name and the deleted entry must all be skipped
+        put_entry(&mut img, ROOT_DIR_SECTOR, 0, b"RSOS       ", ATTR_VOLUME_ID, 0, 0);
+        put_entry(&mut img, ROOT_DIR_SECTOR, 1, b"Ah\0e\0l\0l\0o\0", ATTR_LONG_NAME, 0, 0);
+        put_entry(&mut img, ROOT_DIR_SECTOR, 2, b"HELLO   TXT", 0, 2, HELLO_SIZE as u32);
+        put_entry(&mut img, ROOT_DIR_SECTOR, 3, b"\xe5LD     TXT", 0, 0, 0);
+        put_entry(&mut img, ROOT_DIR_SECTOR, 4, b"SUB        ", ATTR_DIRECTORY, 4, 0);
+
+        put_entry(&mut img, cluster_sector(4), 0, b".          ", ATTR_DIRECTORY, 4, 0);
+        put_entry(&mut img, cluster_sector(4), 1, b"..         ", ATTR_DIRECTORY, 0, 0);
+        put_entry(&mut img, cluster_sector(4), 2, b"INNER   BIN", 0, 5, 10);
+
+        for idx in 0..HELLO_SIZE {
+            img[cluster_sector(2) * SECTOR + idx] = hello_byte(idx);
+        }
+        img[cluster_sector(5) * SECTOR..][..10].copy_from_slice(b"inner data");
+
+        img
+    }
+
+    fn names(fs: &FatFs<RamDisk>, path: &str) -> Result<Vec<String>, FatError> {
+        let dir = match path {
+            "" => fs.root_dir()?,
+            path => fs.read_dir(&fs.find(path)?)?,
+        };
+
+        dir.map(|entry| entry.map(|entry| entry.name().to_string())).collect()
+    }
+
+    #[test]
+    fn lists_directories() {
+        let mut img = image();
+        let device = RamDisk::new(&mut img, SECTOR).unwrap();
+        let mut scratch = [0u8; SECTOR];
+        let fs = FatFs::mount(&device, 0, &mut scratch).unwrap();
+
+        assert_eq!(fs.fat_type(), FatType::Fat12);
+        assert_eq!(names(&fs, "").unwrap(), ["HELLO.TXT", "SUB"]);
+        assert_eq!(names(&fs, "sub").unwrap(), [".", "..", "INNER.BIN"]);
+        assert!(matches!(names(&fs, "HELLO.TXT"), Err(FatError::NotADirectory)));
+        assert!(matches!(names(&fs, "NOPE"), Err(FatError::NotFound)));
+    }
+
+    #[test]
+    fn reads_files_across_clusters() {
+        let mut img = image();
+        let device = RamDisk::new(&mut img, SECTOR).unwrap();
+        let mut scratch = [0u8; SECTOR];
+        let fs = FatFs::mount(&device, 0, &mut scratch).unwrap();
+
+        let mut buf = [0u8; 1024];
+        assert_eq!(fs.read_path("/hello.txt", &mut buf).unwrap(), HELLO_SIZE);
+        assert!(buf[..HELLO_SIZE].iter().enumerate().all(|(idx, &byte)| byte == hello_byte(idx)));
+
+        // starts in the first cluster and ends in the second one
+        let file = fs.find("HELLO.TXT").unwrap();
+        let mut buf = [0u8; 20];
+        assert_eq!(fs.read_file(&file, 500, &mut buf).unwrap(), 20);
+        assert!(buf.iter().enumerate().all(|(idx, &byte)| byte == hello_byte(500 + idx)));
+
+        // reads stop at the end of the file
+        assert_eq!(fs.read_file(&file, 590, &mut buf).unwrap(), 10);
+        assert_eq!(fs.read_file(&file, HELLO_SIZE, &mut buf).unwrap(), 0);
+
+        let mut buf = [0u8; 16];
+        assert_eq!(fs.read_path("SUB/INNER.BIN", &mut buf).unwrap(), 10);
+        assert_eq!(&buf[..10], b"inner data");
+    }
+
+    #[test]
+    fn rejects_small_blocks_and_scratch() {
+        let mut img = image();
+        let device = RamDisk::new(&mut img, 256).unwrap();
+        let mut scratch = [0u8; SECTOR];
+        assert!(matches!(FatFs::mount(&device, 0, &mut scratch), Err(FatError::SectorSizeMismatch)));
+
+        let mut img = image();
+        let device = RamDisk::new(&mut img, SECTOR).unwrap();
+        let mut scratch = [0u8; SECTOR - 1];
+        assert!(matches!(FatFs::mount(&device, 0, &mut scratch), Err(FatError::ScratchTooSmall)));
+    }
+
+    #[test]
+    fn rejects_too_many_clusters() {
+        let mut img = image();
+        img[19..21].copy_from_slice(&0u16.to_le_bytes());
+        img[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let device = RamDisk::new(&mut img, SECTOR).unwrap();
+        let mut scratch = [0u8; SECTOR];
+        assert!(matches!(FatFs::mount(&device, 0, &mut scratch), Err(FatError::InvalidBpb)));
+    }
+
+    #[test]
+    fn stops_on_cyclic_chains() {
+        let mut img = image();
+
+        // SUB points back to itself and has no end marker, HELLO.TXT loops over its 2 clusters
+        set_fat(&mut img, 4, 4);
+        for idx in 3..SECTOR / DIR_ENTRY_SIZE {
+            put_entry(&mut img, cluster_sector(4), idx, b"FILLER  BIN", 0, 5, 10);
+        }
+        set_fat(&mut img, 3, 2);
+        put_entry(&mut img, ROOT_DIR_SECTOR, 2, b"HELLO   TXT", 0, 2, u32::MAX);
+
+        let device = RamDisk::new(&mut img, SECTOR).unwrap();
+        let mut scratch = [0u8; SECTOR];
+        let fs = FatFs::mount(&device, 0, &mut scratch).unwrap();
+
+        assert!(matches!(names(&fs, "SUB"), Err(FatError::InvalidCluster(4))));
+        assert!(matches!(fs.find("SUB/MISSING"), Err(FatError::InvalidCluster(4))));
+
+        let file = fs.find("HELLO.TXT").unwrap();
+        let mut buf = vec![0u8; 64 * SECTOR];
+        assert!(matches!(fs.read_file(&file, 0, &mut buf), Err(FatError::InvalidCluster(_))));
+    }
+}
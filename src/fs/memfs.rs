@@ -0,0 +1,225 @@
+use super::{DirEntryInfo, FileHandle, Vfs, VfsError};
+use crate::memory::FrameAllocator;
+use core::fmt::{self, Write};
+use spin::Mutex;
+
+// generated files are rendered in the stack, so their contents can't be bigger than this
+const GENERATED_MAX_SIZE: usize = 512;
+
+/*
+ * Contents of a file that are generated every time the file is opened or read (kernel stats, for example).
+ * The size given by `open()` is the size at that moment, so it may not match a later read.
+ */
+pub trait MemFileSource {
+    fn write_to(&self, out: &mut dyn Write) -> fmt::Result;
+}
+
+pub enum MemFileData<'a> {
+    Static(&'a [u8]),
+    Generated(&'a dyn MemFileSource),
+}
+
+pub struct MemFile<'a> {
+    pub name: &'a str,
+    pub data: MemFileData<'a>,
+}
+
+// memory usage of a frame allocator, one `name: value` pair per line
+pub struct FrameStats<'a, A: FrameAllocator>(pub &'a Mutex<A>);
+
+impl<'a, A: FrameAllocator> MemFileSource for FrameStats<'a, A> {
+    fn write_to(&self, out: &mut dyn Write) -> fmt::Result {
+        let stats = self.0.lock().stats();
+        writeln!(out, "total_frames: {}", stats.total_frames)?;
+        writeln!(out, "used_frames: {}", stats.used_frames)?;
+        writeln!(out, "free_frames: {}", stats.free_frames)
+    }
+}
+
+// a `fmt::Write` over a fixed buffer that fails instead of truncating
+struct BufWriter<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl<'b> Write for BufWriter<'b> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let dst = self.buf.get_mut(self.len..self.len + s.len()).ok_or(fmt::Error)?;
+        dst.copy_from_slice(s.as_bytes());
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+impl<'a> MemFile<'a> {
+    // runs `f` with the current contents of the file
+    fn with_contents<R>(&self, f: impl FnOnce(&[u8]) -> R) -> Result<R, VfsError> {
+        match self.data {
+            MemFileData::Static(data) => Ok(f(data)),
+            MemFileData::Generated(source) => {
+                let mut buf = [0u8; GENERATED_MAX_SIZE];
+                let mut writer = BufWriter { buf: &mut buf, len: 0 };
+                source.write_to(&mut writer).map_err(|_| VfsError::BackendErr)?;
+
+                let len = writer.len;
+                Ok(f(&buf[..len]))
+            }
+        }
+    }
+}
+
+/*
+ * A flat, read only, in memory filesystem.
+ * Useful to expose kernel data (e.g. diagnostics) as files without any backing storage.
+ * All the files live in the root directory and the inode of a file is its index in `files`.
+ */
+pub struct MemFs<'a> {
+    files: &'a [MemFile<'a>],
+}
+
+impl<'a> MemFs<'a> {
+    pub const fn new(files: &'a [MemFile<'a>]) -> Self {
+        MemFs { files }
+    }
+}
+
+impl<'a> Vfs for MemFs<'a> {
+    fn open(&self, path: &str) -> Result<FileHandle, VfsError> {
+        if path.is_empty() {
+            return Ok(FileHandle { mount: 0, inode: u64::MAX, size: 0, is_dir: true });
+        }
+
+        let (idx, file) = self.files.iter().enumerate().find(|(_, f)| f.name == path).ok_or(VfsError::NotFound)?;
+        let size = file.with_contents(|data| data.len())?;
+        Ok(FileHandle { mount: 0, inode: idx as u64, size: size as u64, is_dir: false })
+    }
+
+    fn read(&self, file: &FileHandle, offset: usize, buf: &mut [u8]) -> Result<usize, VfsError> {
+        if file.is_dir {
+            return Err(VfsError::IsADirectory);
+        }
+
+        let file = self.files.get(file.inode as usize).ok_or(VfsError::InvalidHandle)?;
+        file.with_contents(|data| {
+            if offset >= data.len() {
+                return 0;
+            }
+
+            let len = buf.len().min(data.len() - offset);
+            buf[..len].copy_from_slice(&data[offset..offset + len]);
+            len
+        })
+    }
+
+    fn readdir(&self, path: &str, f: &mut dyn FnMut(DirEntryInfo)) -> Result<(), VfsError> {
+        if !path.is_empty() {
+            return Err(match self.files.iter().any(|file| file.name == path) {
+                true => VfsError::NotADirectory,
+                false => VfsError::NotFound,
+            });
+        }
+
+        for file in self.files {
+            let size = file.with_contents(|data| data.len())?;
+            f(DirEntryInfo { name: file.name, is_dir: false, size: size as u64 });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{Frame, FrameAllocatorStats, MemoryError};
+    use core::cell::Cell;
+
+    // counts how many times it was rendered
+    struct Counter(Cell<usize>);
+
+    impl MemFileSource for Counter {
+        fn write_to(&self, out: &mut dyn Write) -> fmt::Result {
+            self.0.set(self.0.get() + 1);
+            writeln!(out, "renders: {}", self.0.get())
+        }
+    }
+
+    struct FakeAllocator {
+        used: usize,
+    }
+
+    impl FrameAllocator for FakeAllocator {
+        fn allocate_frame(&mut self) -> Option<Frame> {
+            None
+        }
+
+        fn deallocate_frame(&mut self, _frame: Frame) {}
+
+        fn stats(&self) -> FrameAllocatorStats {
+            FrameAllocatorStats { total_frames: 100, used_frames: self.used, free_frames: 100 - self.used }
+        }
+
+        fn allocate_contiguous(&mut self, _count: usize) -> Result<Frame, MemoryError> {
+            Err(MemoryError::NotEnoughPhyMemory)
+        }
+    }
+
+    struct Big;
+
+    impl MemFileSource for Big {
+        fn write_to(&self, out: &mut dyn Write) -> fmt::Result {
+            (0..=GENERATED_MAX_SIZE).try_for_each(|_| out.write_char('x'))
+        }
+    }
+
+    fn read_all(fs: &MemFs, path: &str) -> Result<String, VfsError> {
+        let file = fs.open(path)?;
+        let mut buf = [0u8; GENERATED_MAX_SIZE];
+        let len = fs.read(&file, 0, &mut buf)?;
+        Ok(String::from_utf8(buf[..len].to_vec()).unwrap())
+    }
+
+    #[test]
+    fn serves_static_and_generated_files() {
+        let counter = Counter(Cell::new(0));
+        let files = [
+            MemFile { name: "version", data: MemFileData::Static(b"rsos") },
+            MemFile { name: "renders", data: MemFileData::Generated(&counter) },
+        ];
+        let fs = MemFs::new(&files);
+
+        assert_eq!(read_all(&fs, "version").unwrap(), "rsos");
+
+        // the contents are generated again on every open and read
+        assert_eq!(read_all(&fs, "renders").unwrap(), "renders: 2\n");
+        let file = fs.open("renders").unwrap();
+        let mut buf = [0u8; 16];
+        let len = fs.read(&file, 9, &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"4\n");
+
+        let mut names = Vec::new();
+        fs.readdir("", &mut |entry| names.push((entry.name.to_string(), entry.size))).unwrap();
+        assert_eq!(names, [("version".to_string(), 4), ("renders".to_string(), 11)]);
+        assert!(matches!(fs.readdir("version", &mut |_| {}), Err(VfsError::NotADirectory)));
+    }
+
+    #[test]
+    fn renders_frame_stats() {
+        let allocator = Mutex::new(FakeAllocator { used: 42 });
+        let stats = FrameStats(&allocator);
+        let files = [MemFile { name: "meminfo", data: MemFileData::Generated(&stats) }];
+        let fs = MemFs::new(&files);
+
+        assert_eq!(read_all(&fs, "meminfo").unwrap(), "total_frames: 100\nused_frames: 42\nfree_frames: 58\n");
+
+        allocator.lock().used = 50;
+        assert_eq!(read_all(&fs, "meminfo").unwrap(), "total_frames: 100\nused_frames: 50\nfree_frames: 50\n");
+    }
+
+    #[test]
+    fn rejects_oversized_generated_files() {
+        let files = [MemFile { name: "big", data: MemFileData::Generated(&Big) }];
+        let fs = MemFs::new(&files);
+        assert!(matches!(fs.open("big"), Err(VfsError::BackendErr)));
+    }
+}
@@ -0,0 +1,208 @@
+pub mod fat;
+pub mod memfs;
+
+// the max amount of filesystems that may be mounted at the same time
+const MAX_MOUNTS: usize = 8;
+
+#[derive(Debug)]
+pub enum VfsError {
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    InvalidHandle,
+    MountTableFull,
+    AlreadyMounted,
+    InvalidMountPoint,
+    BackendErr,
+}
+
+/*
+ * An opened file or directory.
+ * `inode` and `size` are backend specific and `mount` is only meaningful to the `MountTable` that opened it.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct FileHandle {
+    pub(crate) mount: usize,
+    pub(crate) inode: u64,
+    pub(crate) size: u64,
+    pub(crate) is_dir: bool,
+}
+
+impl FileHandle {
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+}
+
+pub struct DirEntryInfo<'n> {
+    pub name: &'n str,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+/*
+ * Common interface for every filesystem backend.
+ * Paths given to a backend are relative to its mount point (without the leading '/').
+ * The callback given to `readdir()` is called once per entry.
+ */
+pub trait Vfs {
+    fn open(&self, path: &str) -> Result<FileHandle, VfsError>;
+    fn read(&self, file: &FileHandle, offset: usize, buf: &mut [u8]) -> Result<usize, VfsError>;
+    fn readdir(&self, path: &str, f: &mut dyn FnMut(DirEntryInfo)) -> Result<(), VfsError>;
+}
+
+#[derive(Clone, Copy)]
+struct Mount<'a> {
+    prefix: &'a str, // always normalized (no leading or trailing '/')
+    fs: &'a dyn Vfs,
+}
+
+/*
+ * Maps path prefixes to filesystems. A path is resolved by the mount with the longest prefix that matches
+ * whole path components, so mounting at `/` gives a fallback for every path.
+ */
+pub struct MountTable<'a> {
+    mounts: [Option<Mount<'a>>; MAX_MOUNTS],
+}
+
+fn normalize(path: &str) -> &str {
+    path.trim_matches('/')
+}
+
+impl<'a> MountTable<'a> {
+    pub const fn new() -> Self {
+        MountTable {
+            mounts: [None; MAX_MOUNTS],
+        }
+    }
+
+    pub fn mount(&mut self, mount_point: &'a str, fs: &'a dyn Vfs) -> Result<(), VfsError> {
+        if !mount_point.starts_with('/') {
+            return Err(VfsError::InvalidMountPoint);
+        }
+
+        let prefix = normalize(mount_point);
+        if self.mounts.iter().flatten().any(|m| m.prefix == prefix) {
+            return Err(VfsError::AlreadyMounted);
+        }
+
+        let slot = self.mounts.iter_mut().find(|m| m.is_none()).ok_or(VfsError::MountTableFull)?;
+        *slot = Some(Mount { prefix, fs });
+        Ok(())
+    }
+
+    // returns the index of the mount that owns `path` and the path relative to that mount
+    fn resolve<'p>(&self, path: &'p str) -> Result<(usize, &'p str), VfsError> {
+        let path = normalize(path);
+        let mut best: Option<(usize, &'p str, usize)> = None;
+
+        for (idx, mount) in self.mounts.iter().enumerate() {
+            let Some(mount) = mount else {
+                continue;
+            };
+
+            let rest = if mount.prefix.is_empty() {
+                Some(path)
+            } else {
+                // the prefix must end at a component boundary (`/mnt` does not own `/mntx`)
+                path.strip_prefix(mount.prefix).and_then(|rest| match rest {
+                    "" => Some(""),
+                    _ => rest.strip_prefix('/'),
+                })
+            };
+
+            if let Some(rest) = rest {
+                if best.is_none_or(|(_, _, len)| mount.prefix.len() > len) {
+                    best = Some((idx, rest, mount.prefix.len()));
+                }
+            }
+        }
+
+        best.map(|(idx, rest, _)| (idx, rest)).ok_or(VfsError::NotFound)
+    }
+
+    fn fs(&self, mount: usize) -> Result<&'a dyn Vfs, VfsError> {
+        self.mounts.get(mount).copied().flatten().map(|m| m.fs).ok_or(VfsError::InvalidHandle)
+    }
+}
+
+impl<'a> Vfs for MountTable<'a> {
+    fn open(&self, path: &str) -> Result<FileHandle, VfsError> {
+        let (mount, rest) = self.resolve(path)?;
+        let mut file = self.fs(mount)?.open(rest)?;
+        file.mount = mount;
+        Ok(file)
+    }
+
+    fn read(&self, file: &FileHandle, offset: usize, buf: &mut [u8]) -> Result<usize, VfsError> {
+        self.fs(file.mount)?.read(file, offset, buf)
+    }
+
+    fn readdir(&self, path: &str, f: &mut dyn FnMut(DirEntryInfo)) -> Result<(), VfsError> {
+        let (mount, rest) = self.resolve(path)?;
+        self.fs(mount)?.readdir(rest, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memfs::{MemFile, MemFileData, MemFs};
+
+    fn read_all(vfs: &dyn Vfs, path: &str) -> Result<Vec<u8>, VfsError> {
+        let file = vfs.open(path)?;
+        let mut buf = [0u8; 64];
+        let len = vfs.read(&file, 0, &mut buf)?;
+        Ok(buf[..len].to_vec())
+    }
+
+    #[test]
+    fn resolves_paths_to_the_longest_mount() {
+        let root_files = [
+            MemFile { name: "motd", data: MemFileData::Static(b"root motd") },
+            MemFile { name: "procx", data: MemFileData::Static(b"not proc") },
+        ];
+        let proc_files = [MemFile { name: "motd", data: MemFileData::Static(b"proc motd") }];
+        let root = MemFs::new(&root_files);
+        let proc = MemFs::new(&proc_files);
+
+        let mut mounts = MountTable::new();
+        mounts.mount("/", &root).unwrap();
+        mounts.mount("/proc/", &proc).unwrap();
+
+        assert_eq!(read_all(&mounts, "/motd").unwrap(), b"root motd");
+        assert_eq!(read_all(&mounts, "/proc/motd").unwrap(), b"proc motd");
+        assert_eq!(read_all(&mounts, "proc/motd/").unwrap(), b"proc motd");
+
+        // `/proc` only owns whole path components
+        assert_eq!(read_all(&mounts, "/procx").unwrap(), b"not proc");
+        assert!(mounts.open("/proc").unwrap().is_dir());
+        assert!(matches!(mounts.open("/proc/procx"), Err(VfsError::NotFound)));
+
+        let mut names = Vec::new();
+        mounts.readdir("/proc", &mut |entry| names.push(entry.name.to_string())).unwrap();
+        assert_eq!(names, ["motd"]);
+    }
+
+    #[test]
+    fn rejects_bad_mounts() {
+        let fs = MemFs::new(&[]);
+        let mut mounts = MountTable::new();
+
+        assert!(matches!(mounts.resolve("/anything"), Err(VfsError::NotFound)));
+        assert!(matches!(mounts.mount("proc", &fs), Err(VfsError::InvalidMountPoint)));
+
+        mounts.mount("/proc", &fs).unwrap();
+        assert!(matches!(mounts.mount("/proc/", &fs), Err(VfsError::AlreadyMounted)));
+
+        for idx in 1..MAX_MOUNTS {
+            let mount_point: &'static str = Box::leak(format!("/mnt{idx}").into_boxed_str());
+            mounts.mount(mount_point, &fs).unwrap();
+        }
+        assert!(matches!(mounts.mount("/full", &fs), Err(VfsError::MountTableFull)));
+    }
+}
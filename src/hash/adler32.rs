@@ -0,0 +1,83 @@
+// https://en.wikipedia.org/wiki/Adler-32
+const MOD_ADLER: u32 = 65521;
+
+/*
+ * The sums can be accumulated for this many bytes before a `% MOD_ADLER` is needed without overflowing an u32.
+ * This is the same constant used by zlib.
+ */
+const NMAX: usize = 5552;
+
+/*
+ * Incremental Adler-32.
+ * The data may be fed in any number of `update()` calls, the result only depends on the concatenation of all of them.
+ */
+#[derive(Clone, Copy)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    pub const fn new() -> Self {
+        Adler32 { a: 1, b: 0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(NMAX) {
+            for &byte in chunk {
+                self.a += byte as u32;
+                self.b += self.a;
+            }
+
+            self.a %= MOD_ADLER;
+            self.b %= MOD_ADLER;
+        }
+    }
+
+    pub fn finalize(self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+// one shot helper, e.g. `checksum(b"Wikipedia") == 0x11e60398`
+pub fn checksum(data: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(data);
+    adler.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_known_vectors() {
+        assert_eq!(checksum(b""), 1);
+        assert_eq!(checksum(b"Wikipedia"), 0x11e60398);
+    }
+
+    #[test]
+    fn split_updates_match_one_shot() {
+        let data = b"Wikipedia, the free encyclopedia";
+
+        for split in [0, 1, 9, data.len() - 1, data.len()] {
+            let mut adler = Adler32::new();
+            adler.update(&data[..split]);
+            adler.update(&data[split..]);
+            assert_eq!(adler.finalize(), checksum(data));
+        }
+    }
+
+    #[test]
+    fn handles_inputs_longer_than_nmax() {
+        // all 0xff is the worst case for the sums, they would overflow without the `% MOD_ADLER` every NMAX bytes
+        let data = [0xffu8; 20000];
+        assert!(data.len() > 3 * NMAX);
+        assert_eq!(checksum(&data), 0x9f51d664);
+
+        // the reductions must not depend on how the data is split between calls
+        let mut adler = Adler32::new();
+        data.chunks(NMAX - 1).for_each(|chunk| adler.update(chunk));
+        assert_eq!(adler.finalize(), 0x9f51d664);
+    }
+}
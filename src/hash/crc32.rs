@@ -0,0 +1,87 @@
+// https://en.wikipedia.org/wiki/Cyclic_redundancy_check
+// this is the CRC-32 used by zlib, PNG and GPT (reflected, polynomial 0x04C11DB7)
+const POLYNOMIAL: u32 = 0xedb88320; // 0x04C11DB7 with the bits reversed
+
+// the lookup table is built at compile time so the checksum can process a whole byte per step
+const TABLE: [u32; 256] = make_table();
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+/*
+ * Incremental CRC-32.
+ * The data may be fed in any number of `update()` calls, the result only depends on the concatenation of all of them.
+ */
+#[derive(Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    pub const fn new() -> Self {
+        Crc32 { state: 0xffffffff }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            let idx = ((self.state ^ byte as u32) & 0xff) as usize;
+            self.state = (self.state >> 8) ^ TABLE[idx];
+        }
+    }
+
+    pub fn finalize(self) -> u32 {
+        !self.state
+    }
+}
+
+// one shot helper, e.g. `checksum(b"123456789") == 0xcbf43926`
+pub fn checksum(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_known_vectors() {
+        assert_eq!(checksum(b""), 0);
+        assert_eq!(checksum(b"123456789"), 0xcbf43926);
+        assert_eq!(checksum(b"The quick brown fox jumps over the lazy dog"), 0x414fa339);
+    }
+
+    #[test]
+    fn split_updates_match_one_shot() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+
+        for split in [0, 1, 9, data.len() - 1, data.len()] {
+            let mut crc = Crc32::new();
+            crc.update(&data[..split]);
+            crc.update(&data[split..]);
+            assert_eq!(crc.finalize(), checksum(data));
+        }
+
+        let mut crc = Crc32::new();
+        data.chunks(3).for_each(|chunk| crc.update(chunk));
+        assert_eq!(crc.finalize(), 0x414fa339);
+    }
+}
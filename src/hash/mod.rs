@@ -0,0 +1,3 @@
+// lightweight (non cryptographic) checksums, useful to verify modules, config blobs and on disk structures
+pub mod crc32;
+pub mod adler32;
@@ -1,14 +1,21 @@
-#![no_std]
-#![no_main]
+// the unit tests run as a normal (std) program on the host
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 
 mod multiboot2;
 mod vga_buffer;
 mod memory;
+mod boot;
+mod hash;
+mod storage;
+mod fs;
+mod cpu;
 
 use core::panic::PanicInfo;
-use multiboot2::{elf_symbols::ElfSymbols, memory_map::{MemoryMap, MemoryMapEntryType}, MbBootInfo};
+use multiboot2::{boot_loader_name::BootLoaderName, cmd_line::CmdLine, elf_symbols::ElfSymbols, memory_map::MemoryMap, MbBootInfo};
 // use memory::{FrameAllocator, SimpleFrameAllocator};
 
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     println!("{}", info);
@@ -29,10 +36,7 @@ fn print_mem_status(mb_info: &MbBootInfo) {
         );
     }
 
-    let total_memory: u64 = mem_map_entries.into_iter()
-        .filter(|entry| entry.entry_type() == MemoryMapEntryType::AvailableRAM)
-        .map(|entry| entry.length)
-        .sum();
+    let total_memory = mb_info.total_usable_memory();
     println!(
         "Total (available) memory: {} bytes ({:.2} GB)",
         total_memory,
@@ -40,9 +44,31 @@ fn print_mem_status(mb_info: &MbBootInfo) {
     );
 }
 
+fn print_boot_params(mb_info: &MbBootInfo) {
+    // both tags are optional and only informative, so neither a missing nor a malformed tag stops the boot
+    if let Some(name) = mb_info.get_tag::<BootLoaderName>() {
+        match name.string() {
+            Ok(name) => { println!("Bootloader: {}", name); }
+            Err(err) => { println!("Bootloader: <invalid: {:?}>", err); }
+        }
+    }
+
+    if let Some(cmd_line) = mb_info.get_tag::<CmdLine>() {
+        match cmd_line.string() {
+            Ok(cmd_line) => { println!("Command line: {}", cmd_line); }
+            Err(err) => { println!("Command line: <invalid: {:?}>", err); }
+        }
+    }
+}
+
+#[cfg(not(test))]
 #[no_mangle]
 pub extern "C" fn main(mb_boot_info_addr: *const u8) -> ! {
+    boot::early_print("rsos: entered rust code\n");
+
     let mb_info = unsafe { MbBootInfo::new(mb_boot_info_addr) }.expect("Invalid mb2 data.");
+    mb_info.validate().expect("Corrupted mb2 tag list.");
+    print_boot_params(&mb_info);
     print_mem_status(&mb_info);
 
     let mem_map = mb_info.get_tag::<MemoryMap>().expect("Memory map tag is not present");
@@ -0,0 +1,91 @@
+use super::VirtualAddress;
+use bitflags::bitflags;
+use core::arch::asm;
+
+// the cr3 register holds the physical address of the active P4 table
+pub struct CR3;
+
+impl CR3 {
+    /*
+     * Removes the TLB entry (if any) for the page containing `addr`.
+     * Must be called after changing or removing a mapping that the cpu may have already cached.
+     */
+    pub fn invalidate_entry(addr: VirtualAddress) {
+        // invlpg is privileged and the host tests don't use their tables for translation anyway
+        if cfg!(test) {
+            return;
+        }
+
+        unsafe { asm!("invlpg [{}]", in(reg) addr, options(nostack, preserves_flags)) };
+    }
+
+    /*
+     * Flushes the whole TLB by writing cr3 back to itself.
+     * Entries for pages mapped with `EntryFlags::GLOBAL` survive this (if global pages are enabled in cr4).
+     */
+    pub fn invalidate_all() {
+        unsafe {
+            asm!(
+                "mov {0}, cr3",
+                "mov cr3, {0}",
+                out(reg) _,
+                options(nostack, preserves_flags)
+            )
+        };
+    }
+}
+
+// the cr2 register holds the virtual address that caused the last page fault
+pub struct CR2;
+
+impl CR2 {
+    pub fn get() -> VirtualAddress {
+        let addr: VirtualAddress;
+        unsafe { asm!("mov {}, cr2", out(reg) addr, options(nomem, nostack, preserves_flags)) };
+        addr
+    }
+}
+
+bitflags! {
+    /*
+     * The error code pushed by the cpu on a page fault, the faulting address is in cr2.
+     * Decode it with `from_bits_truncate()`, bits that are not known here (protection keys, shadow stacks, ...) are dropped.
+     */
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PageFaultErrorCode: u64 {
+        const PRESENT           = 1 << 0; // set: protection violation, clear: the page is not present
+        const WRITE             = 1 << 1; // set: the access was a write, clear: it was a read
+        const USER              = 1 << 2; // the access happened in ring 3
+        const RESERVED_WRITE    = 1 << 3; // a reserved bit was set in one of the page table entries
+        const INSTRUCTION_FETCH = 1 << 4; // the access was an instruction fetch (only with NXE enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PageFaultErrorCode;
+
+    #[test]
+    fn decodes_page_fault_error_codes() {
+        // kernel read of a page that is not present
+        assert_eq!(PageFaultErrorCode::from_bits_truncate(0x0), PageFaultErrorCode::empty());
+
+        // user write to a present, read only page
+        assert_eq!(
+            PageFaultErrorCode::from_bits_truncate(0x7),
+            PageFaultErrorCode::PRESENT | PageFaultErrorCode::WRITE | PageFaultErrorCode::USER
+        );
+
+        // kernel instruction fetch from a NO_EXECUTE page
+        assert_eq!(
+            PageFaultErrorCode::from_bits_truncate(0x11),
+            PageFaultErrorCode::PRESENT | PageFaultErrorCode::INSTRUCTION_FETCH
+        );
+
+        // reserved bit set in an entry, with the unknown protection key bit (5) dropped
+        assert_eq!(
+            PageFaultErrorCode::from_bits_truncate(0x29),
+            PageFaultErrorCode::PRESENT | PageFaultErrorCode::RESERVED_WRITE
+        );
+    }
+}
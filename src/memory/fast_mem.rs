@@ -0,0 +1,128 @@
+use core::arch::asm;
+
+// below this size, the setup cost of `rep` is not worth it
+const REP_THRESHOLD: usize = 64;
+
+/*
+ * Fills `bytes` bytes starting at `ptr` with `byte`.
+ * The unaligned head and the tail are written byte by byte and the aligned bulk is written 8 bytes at a time with `rep stosq`.
+ *
+ * Safety: `ptr` must be valid for writes of `bytes` bytes.
+ */
+pub unsafe fn fast_fill(ptr: *mut u8, byte: u8, bytes: usize) {
+    if bytes < REP_THRESHOLD {
+        ptr.write_bytes(byte, bytes);
+        return;
+    }
+
+    let head = ptr.align_offset(size_of::<u64>()).min(bytes);
+    ptr.write_bytes(byte, head);
+
+    let qwords = (bytes - head) / size_of::<u64>();
+    let pattern = byte as u64 * 0x0101_0101_0101_0101;
+    asm!(
+        "rep stosq",
+        inout("rdi") ptr.add(head) => _,
+        inout("rcx") qwords => _,
+        in("rax") pattern,
+        options(nostack, preserves_flags)
+    );
+
+    let done = head + qwords * size_of::<u64>();
+    ptr.add(done).write_bytes(byte, bytes - done);
+}
+
+/*
+ * Zeroes `bytes` bytes starting at `ptr`, see `fast_fill()`.
+ *
+ * Safety: `ptr` must be valid for writes of `bytes` bytes.
+ */
+pub unsafe fn fast_zero(ptr: *mut u8, bytes: usize) {
+    fast_fill(ptr, 0, bytes);
+}
+
+/*
+ * Copies `bytes` bytes from `src` to `dst` using `rep movsq` for the bulk of the copy.
+ * The destination is aligned first (unaligned loads are cheaper than unaligned stores).
+ *
+ * Safety: Both pointers must be valid for `bytes` bytes and the two regions must *not* overlap
+ * (the copy always goes forward, so an overlapping `dst > src` would read already overwritten bytes).
+ * Use `core::ptr::copy` for overlapping regions.
+ */
+pub unsafe fn fast_copy(dst: *mut u8, src: *const u8, bytes: usize) {
+    if bytes < REP_THRESHOLD {
+        dst.copy_from_nonoverlapping(src, bytes);
+        return;
+    }
+
+    let head = dst.align_offset(size_of::<u64>()).min(bytes);
+    dst.copy_from_nonoverlapping(src, head);
+
+    let qwords = (bytes - head) / size_of::<u64>();
+    asm!(
+        "rep movsq",
+        inout("rdi") dst.add(head) => _,
+        inout("rsi") src.add(head) => _,
+        inout("rcx") qwords => _,
+        options(nostack, preserves_flags)
+    );
+
+    let done = head + qwords * size_of::<u64>();
+    dst.add(done).copy_from_nonoverlapping(src.add(done), bytes - done);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // u64s so that the buffer itself is 8 byte aligned and every start offset below is tried
+    const BUF_QWORDS: usize = 64;
+    const BUF_LEN: usize = BUF_QWORDS * size_of::<u64>();
+
+    // fills [start, start + len) of a buffer full of 0xaa with `fill` and checks that nothing else was touched
+    fn check_fill(start: usize, len: usize, fill: impl FnOnce(*mut u8, usize)) -> [u8; BUF_LEN] {
+        let mut buf = [0xaaaa_aaaa_aaaa_aaaa_u64; BUF_QWORDS];
+        let ptr = buf.as_mut_ptr().cast::<u8>();
+        fill(unsafe { ptr.add(start) }, len);
+
+        let bytes: [u8; BUF_LEN] = unsafe { core::mem::transmute(buf) };
+        assert!(bytes[..start].iter().all(|&b| b == 0xaa), "head overwritten (start {start}, len {len})");
+        assert!(bytes[start + len..].iter().all(|&b| b == 0xaa), "tail overwritten (start {start}, len {len})");
+        bytes
+    }
+
+    #[test]
+    fn fills_unaligned_ranges() {
+        for start in 0..size_of::<u64>() {
+            for len in [0, 1, 7, 63, 64, 65, 200, 301] {
+                let bytes = check_fill(start, len, |ptr, len| unsafe { fast_fill(ptr, 0x5c, len) });
+                assert!(bytes[start..start + len].iter().all(|&b| b == 0x5c), "start {start}, len {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn zeroes_unaligned_ranges() {
+        for start in 0..size_of::<u64>() {
+            for len in [0, 3, 64, 129, 500] {
+                let bytes = check_fill(start, len, |ptr, len| unsafe { fast_zero(ptr, len) });
+                assert!(bytes[start..start + len].iter().all(|&b| b == 0), "start {start}, len {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn copies_like_copy_nonoverlapping() {
+        let src: Vec<u8> = (0..BUF_LEN).map(|i| (i * 7 + 3) as u8).collect();
+
+        for (dst_start, src_start) in [(0, 0), (3, 0), (0, 5), (1, 6), (7, 7)] {
+            for len in [0, 1, 8, 13, 63, 64, 72, 100, 255, 400] {
+                let mut expected = [0xaau8; BUF_LEN];
+                unsafe { core::ptr::copy_nonoverlapping(src.as_ptr().add(src_start), expected.as_mut_ptr().add(dst_start), len) };
+
+                let bytes = check_fill(dst_start, len, |ptr, len| unsafe { fast_copy(ptr, src.as_ptr().add(src_start), len) });
+                assert_eq!(bytes, expected, "dst {dst_start}, src {src_start}, len {len}");
+            }
+        }
+    }
+}
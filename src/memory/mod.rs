@@ -1,15 +1,34 @@
 mod simple_frame_allocator;
 mod paging;
+mod fast_mem;
+mod cr3;
 
-const PAGE_SIZE: usize = 4096;
+use fast_mem::fast_zero;
+use core::fmt;
+use paging::Paging;
+
+pub(crate) const PAGE_SIZE: usize = 4096;
 
 pub type PhysicalAddress = usize;
 pub type VirtualAddress = usize;
 
 #[repr(transparent)]
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct Frame(usize); // this usize is the frame index in the physical memory
 
+// the index alone is not very useful in logs, so the start addr is printed as well
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Frame(idx={} addr=0x{:x})", self.0, self.addr())
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
 impl Frame {
     fn from_phy_addr(addr: PhysicalAddress) -> Frame {
         Frame(addr / PAGE_SIZE)
@@ -18,9 +37,175 @@ impl Frame {
     fn addr(&self) -> PhysicalAddress {
         self.0 * PAGE_SIZE
     }
+
+    /*
+     * Returns all the frames that contain at least one byte of [start_addr, end_addr] (both inclusive).
+     * This means that `end_addr` is the last byte of the range and not the byte after it.
+     */
+    pub fn range_inclusive(start_addr: PhysicalAddress, end_addr: PhysicalAddress) -> FrameRange {
+        let start = Frame::from_phy_addr(start_addr);
+        let end = Frame::from_phy_addr(end_addr);
+        FrameRange::new(start, (end.0 + 1).saturating_sub(start.0))
+    }
+
+    pub fn next(&self) -> Frame {
+        Frame(self.0 + 1)
+    }
+}
+
+// `count` consecutive frames, starting at `start`
+#[derive(Clone, Copy)]
+pub struct FrameRange {
+    next: Frame,
+    remaining: usize,
+}
+
+impl FrameRange {
+    pub fn new(start: Frame, count: usize) -> Self {
+        FrameRange { next: start, remaining: count }
+    }
+}
+
+impl Iterator for FrameRange {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let frame = self.next;
+        self.next = frame.next();
+        self.remaining -= 1;
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for FrameRange {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameAllocatorStats {
+    pub total_frames: usize,
+    pub used_frames: usize,
+    pub free_frames: usize,
 }
 
 pub trait FrameAllocator {
     fn allocate_frame(&mut self) -> Option<Frame>;
     fn deallocate_frame(&mut self, frame: Frame);
+    fn stats(&self) -> FrameAllocatorStats;
+
+    // returns the first of `count` frames with consecutive physical addresses
+    fn allocate_contiguous(&mut self, count: usize) -> Result<Frame, MemoryError>;
+
+    // gives back `count` frames starting at `frame` (the last one first)
+    fn deallocate_contiguous(&mut self, frame: Frame, count: usize) {
+        for idx in (frame.0..frame.0 + count).rev() {
+            self.deallocate_frame(Frame(idx));
+        }
+    }
+
+    /*
+     * Allocates a frame and fills it with zeros.
+     * The frame might not be mapped anywhere, so it is temporarily mapped through `paging` to clear it.
+     * If that fails, the frame is given back with `deallocate_frame()` (which may leak it, see `SimpleFrameAllocator`).
+     */
+    fn allocate_zeroed_frame(&mut self, paging: &mut Paging) -> Result<Frame, MemoryError> where Self: Sized {
+        let frame = self.allocate_frame().ok_or(MemoryError::NotEnoughPhyMemory)?;
+
+        if let Err(err) = paging.with_mapped_frame(frame, self, |ptr| unsafe { fast_zero(ptr, PAGE_SIZE) }) {
+            self.deallocate_frame(frame);
+            return Err(err);
+        }
+
+        Ok(frame)
+    }
+}
+
+#[derive(Debug)]
+pub enum MemoryError {
+    UnmappingUnusedTableEntry,
+    CannotUnmapHugePage,
+    TemporaryPageInUse,
+    MappingUsedTableEntry,
+    HugePageNotAligned,
+    NotEnoughPhyMemory,
+    InvalidRange,
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MemoryError::NotEnoughPhyMemory => "out of physical memory",
+            MemoryError::UnmappingUnusedTableEntry => "tried to unmap an unused entry",
+            MemoryError::CannotUnmapHugePage => "tried to unmap or update a single page of a huge page",
+            MemoryError::TemporaryPageInUse => "the temporary page is already in use",
+            MemoryError::MappingUsedTableEntry => "tried to map an already used entry",
+            MemoryError::HugePageNotAligned => "huge page or frame is not aligned to the huge page size",
+            MemoryError::InvalidRange => "invalid (empty, overflowing or non canonical) address range",
+        })
+    }
+}
+
+impl core::error::Error for MemoryError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(start_addr: PhysicalAddress, end_addr: PhysicalAddress) -> Vec<Frame> {
+        Frame::range_inclusive(start_addr, end_addr).collect()
+    }
+
+    #[test]
+    fn frame_ranges_include_partial_frames() {
+        // a single byte and a whole frame
+        assert_eq!(frames(0x1000, 0x1000), [Frame(1)]);
+        assert_eq!(frames(0x1000, 0x1fff), [Frame(1)]);
+
+        // the frames of the first and the last byte count even if they are only partially covered
+        assert_eq!(frames(0x1fff, 0x3000), [Frame(1), Frame(2), Frame(3)]);
+        assert_eq!(frames(0x1800, 0x27ff), [Frame(1), Frame(2)]);
+        assert_eq!(frames(0, 10 * PAGE_SIZE - 1).len(), 10);
+    }
+
+    #[test]
+    fn frame_ranges_ending_before_the_start_are_empty() {
+        assert!(frames(0x3000, 0x1000).is_empty());
+        assert!(frames(0x2000, 0x1fff).is_empty());
+    }
+
+    #[test]
+    fn frame_ranges_know_their_length() {
+        let mut range = Frame::range_inclusive(0x1fff, 0x3000);
+        assert_eq!(range.len(), 3);
+        range.next();
+        assert_eq!(range.len(), 2);
+        assert_eq!(range.collect::<Vec<_>>(), [Frame(2), Frame(3)]);
+        assert_eq!(Frame::range_inclusive(0x3000, 0x1000).len(), 0);
+
+        assert_eq!(FrameRange::new(Frame(5), 2).collect::<Vec<_>>(), [Frame(5), Frame(6)]);
+        assert_eq!(FrameRange::new(Frame(5), 0).next(), None);
+    }
+
+    #[test]
+    fn frames_print_their_addr() {
+        assert_eq!(format!("{:?}", Frame(1)), "Frame(idx=1 addr=0x1000)");
+        assert_eq!(format!("{}", Frame::from_phy_addr(0xb8fff)), "Frame(idx=184 addr=0xb8000)");
+        assert_eq!(format!("{:?}", [Frame(0)]), "[Frame(idx=0 addr=0x0)]");
+    }
+
+    #[test]
+    fn memory_errors_have_messages() {
+        assert_eq!(format!("{}", MemoryError::NotEnoughPhyMemory), "out of physical memory");
+        assert_eq!(format!("{}", MemoryError::MappingUsedTableEntry), "tried to map an already used entry");
+        assert_eq!(format!("{}", MemoryError::InvalidRange), "invalid (empty, overflowing or non canonical) address range");
+
+        // Debug is still the variant name
+        assert_eq!(format!("{:?}", MemoryError::TemporaryPageInUse), "TemporaryPageInUse");
+    }
 }
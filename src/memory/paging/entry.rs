@@ -2,6 +2,7 @@ use crate::memory::{Frame, PhysicalAddress};
 use bitflags::bitflags;
 
 bitflags! {
+    #[derive(Clone, Copy, Debug)]
     pub struct EntryFlags: u64 {
         const PRESENT         = 1 << 0;  // the page is currently in memory
         const WRITABLE        = 1 << 1;  // it’s allowed to write to this page
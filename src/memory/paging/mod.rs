@@ -1,16 +1,47 @@
 mod entry;
 mod table;
 
-use super::{Frame, FrameAllocator, PhysicalAddress, VirtualAddress, PAGE_SIZE};
-use core::{marker::PhantomData, ptr::NonNull};
-use entry::EntryFlags;
+use super::{cr3::CR3, Frame, FrameAllocator, MemoryError, PhysicalAddress, VirtualAddress, PAGE_SIZE};
+use core::{fmt, marker::PhantomData, ops::Range, ptr::NonNull};
+use entry::{Entry, EntryFlags};
 use table::{Level4, Table, P4};
-use crate::{print, println};
+use crate::{cpu::efer::{self, Efer}, print, println};
 // use core::arch::asm;
 
+/*
+ * `unmap_range` flushes the whole TLB once instead of doing an `invlpg` per page when it is asked to unmap more pages than this.
+ * This can be tuned, a full flush is cheap by itself but every translation has to be walked again afterwards.
+ */
+const INVALIDATE_ALL_THRESHOLD: usize = 32;
+
 const ENTRY_COUNT: usize = 512; // 512 = 2^9 = log2(PAGE_SIZE), PAGE_SIZE = 4096
+
+// virtual page reserved for temporary mappings (first page of the P4 entry right before the recursive one)
+const TEMPORARY_PAGE_ADDR: VirtualAddress = 0o177777_776_000_000_000_0000;
+
+// in x86_64, the top 16 bits of a virtual addr must be sign extension bits, so these addrs are invalid
+const NON_CANONICAL: Range<VirtualAddress> = 0x0000_8000_0000_0000..0xffff_8000_0000_0000;
+
+fn is_canonical(addr: VirtualAddress) -> bool {
+    !NON_CANONICAL.contains(&addr)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Page(usize); // this usize is the page index in the virtual memory
 
+// the index alone is not very useful in logs, so the start addr is printed as well
+impl fmt::Debug for Page {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Page(idx={} addr=0x{:x})", self.0, self.addr())
+    }
+}
+
+impl fmt::Display for Page {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
 /* ----------------- SOME NOTES ON PAGE TABLE INDEX CALCULATION -----------------
  * Let´s assume this address: 0xdeadbeef, with 4KiB pages (12 bits)
  * The calculated page index is: 912091 (0xdeadbeef / PAGE_SIZE)
@@ -34,16 +65,41 @@ pub struct Page(usize); // this usize is the page index in the virtual memory
  */
 impl Page {
     fn from_virt_addr(addr: VirtualAddress) -> Page {
-        // in x86_64, the top 16 bits of a virtual addr must be sign extension bits
-        // if they are not, its an invalid addr
-        assert!(
-            addr < 0x0000_8000_0000_0000 || addr >= 0xffff_8000_0000_0000,
-            "Invalid virtual address: 0x{:x}",
-            addr
-        );
+        assert!(is_canonical(addr), "Invalid virtual address: 0x{:x}", addr);
         Page(addr / PAGE_SIZE)
     }
 
+    fn addr(&self) -> VirtualAddress {
+        self.0 * PAGE_SIZE
+    }
+
+    /*
+     * Returns all the pages that contain at least one byte of [start_addr, end_addr] (both inclusive).
+     * This means that `end_addr` is the last byte of the range and not the byte after it.
+     */
+    pub fn range_inclusive(start_addr: VirtualAddress, end_addr: VirtualAddress) -> PageRange {
+        let start = Page::from_virt_addr(start_addr);
+        let count = (Page::from_virt_addr(end_addr).0 + 1).saturating_sub(start.0);
+        PageRange::new(start, count)
+    }
+
+    /*
+     * Returns all the pages that contain at least one byte of [start_addr, end_addr).
+     * Unlike `range_inclusive`, `end_addr` is the byte after the range, so an empty range yields no pages.
+     */
+    pub fn containing_range(start_addr: VirtualAddress, end_addr: VirtualAddress) -> PageRange {
+        let start = Page::from_virt_addr(start_addr);
+        if end_addr <= start_addr {
+            return PageRange::new(start, 0);
+        }
+
+        Page::range_inclusive(start_addr, end_addr - 1)
+    }
+
+    pub fn offset(&self, count: usize) -> Page {
+        Page(self.0 + count)
+    }
+
     fn p4_index(&self) -> usize {
         (self.0 >> 27) & 0o777
     }
@@ -61,6 +117,56 @@ impl Page {
     }
 }
 
+// `count` consecutive pages, starting at `start`
+#[derive(Clone, Copy)]
+pub struct PageRange {
+    next: Page,
+    remaining: usize,
+}
+
+impl PageRange {
+    pub fn new(start: Page, count: usize) -> Self {
+        PageRange { next: start, remaining: count }
+    }
+}
+
+impl Iterator for PageRange {
+    type Item = Page;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let page = self.next;
+        self.next = page.offset(1);
+        self.remaining -= 1;
+        Some(page)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for PageRange {}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HugePageSize {
+    Size2MiB, // mapped by a P2 entry
+    Size1GiB, // mapped by a P3 entry (the cpu must support it, see the `pdpe1gb` cpuid flag)
+}
+
+impl HugePageSize {
+    // the amount of 4KiB pages covered by a huge page
+    fn page_count(&self) -> usize {
+        match self {
+            HugePageSize::Size2MiB => ENTRY_COUNT,
+            HugePageSize::Size1GiB => ENTRY_COUNT * ENTRY_COUNT,
+        }
+    }
+}
+
 /*
  * Safety: Raw pointers are not Send/Sync so `Paging` cannot be used between threads as it would cause data races.
  */
@@ -85,6 +191,12 @@ impl Paging {
         }
     }
 
+    // used by the host tests, where the tables live in regular memory
+    #[cfg(test)]
+    unsafe fn from_p4(p4: NonNull<Table<Level4>>) -> Self {
+        Paging { p4, _marker: PhantomData }
+    }
+
     fn p4(&self) -> &Table<Level4> {
         unsafe { self.p4.as_ref() }
     }
@@ -93,36 +205,189 @@ impl Paging {
         unsafe { self.p4.as_mut() }
     }
 
-    pub fn map_page_to_frame<A: FrameAllocator>( &mut self, page: Page, frame: Frame, frame_allocator: &mut A, flags: EntryFlags) {
+    /*
+     * Maps `page` to `frame`, creating the missing page tables on the way.
+     * Fails if the page is already mapped (huge pages included) or if there is no frame left for a table.
+     * The tables created before a failure are kept (empty tables are valid and will be reused).
+     */
+    pub fn map_page_to_frame<A: FrameAllocator>( &mut self, page: Page, frame: Frame, frame_allocator: &mut A, flags: EntryFlags) -> Result<(), MemoryError> {
         let p4 = self.p4_mut();
-        let p3 = p4.create_next_table(page.p4_index(), frame_allocator);
-        let p2 = p3.create_next_table(page.p3_index(), frame_allocator);
-        let p1 = p2.create_next_table(page.p2_index(), frame_allocator);
+        let p3 = p4.create_next_table(page.p4_index(), frame_allocator)?;
+        let p2 = p3.create_next_table(page.p3_index(), frame_allocator)?;
+        let p1 = p2.create_next_table(page.p2_index(), frame_allocator)?;
 
         // the entry must be unused
-        assert!(!p1.entries[page.p1_index()].is_used());
+        if p1.entries[page.p1_index()].is_used() {
+            return Err(MemoryError::MappingUsedTableEntry);
+        }
 
         p1.entries[page.p1_index()].set(frame, flags | EntryFlags::PRESENT);
+        Ok(())
     }
 
-    pub fn map_page<A: FrameAllocator>( &mut self, page: Page, frame_allocator: &mut A, flags: EntryFlags) {
+    /*
+     * Maps `page` to `frame` with a single P3 (1GiB) or P2 (2MiB) entry, so no lower tables are created.
+     * Both the page and the frame must be aligned to the huge page size.
+     */
+    pub fn map_huge_page<A: FrameAllocator>(&mut self, page: Page, frame: Frame, frame_allocator: &mut A, flags: EntryFlags, size: HugePageSize) -> Result<(), MemoryError> {
+        if !page.0.is_multiple_of(size.page_count()) || !frame.0.is_multiple_of(size.page_count()) {
+            return Err(MemoryError::HugePageNotAligned);
+        }
+
+        // the slot is checked before creating any table, so a failed call doesn't leave new tables behind
+        // (a used slot also means that a huge page never replaces a lower level table)
+        let p3 = self.p4().next_table(page.p4_index());
+        let slot_used = match size {
+            HugePageSize::Size1GiB => p3.is_some_and(|p3| p3.entries[page.p3_index()].is_used()),
+            HugePageSize::Size2MiB => p3.is_some_and(|p3| {
+                // a 1GiB page already covers this whole region
+                p3.entries[page.p3_index()].flags().contains(EntryFlags::HUGE_PAGE)
+                    || p3.next_table(page.p3_index()).is_some_and(|p2| p2.entries[page.p2_index()].is_used())
+            }),
+        };
+
+        if slot_used {
+            return Err(MemoryError::MappingUsedTableEntry);
+        }
+
+        // running out of frames for the P2 table keeps the new P3 table around, just like `map_page_to_frame()`
+        let p3 = self.p4_mut().create_next_table(page.p4_index(), frame_allocator)?;
+        let entry = match size {
+            HugePageSize::Size1GiB => &mut p3.entries[page.p3_index()],
+            HugePageSize::Size2MiB => &mut p3.create_next_table(page.p3_index(), frame_allocator)?.entries[page.p2_index()],
+        };
+
+        entry.set(frame, flags | EntryFlags::PRESENT | EntryFlags::HUGE_PAGE);
+        Ok(())
+    }
+
+    pub fn map_page<A: FrameAllocator>( &mut self, page: Page, frame_allocator: &mut A, flags: EntryFlags) -> Result<(), MemoryError> {
         // get a random (free) frame
-        let frame = frame_allocator.allocate_frame().expect("Out of memory. Could not allocate new frame.");
+        let frame = frame_allocator.allocate_frame().ok_or(MemoryError::NotEnoughPhyMemory)?;
+
+        self.map_page_to_frame(page, frame, frame_allocator, flags).inspect_err(|_| frame_allocator.deallocate_frame(frame))
+    }
+
+    /*
+     * Removes the mapping for `page` and returns the frame it was mapped to.
+     * The frame is *not* deallocated, that is up to the caller (it might still be mapped somewhere else).
+     * Pages that are part of a huge page can't be unmapped on their own, so an error is returned for them.
+     */
+    pub fn unmap_page(&mut self, page: Page) -> Result<Frame, MemoryError> {
+        let frame = self.unmap_page_no_flush(&page)?;
+        CR3::invalidate_entry(page.addr());
 
-        self.map_page_to_frame(page, frame, frame_allocator, flags);
+        Ok(frame)
     }
 
-    pub fn unmap_page(&self) {
-        unimplemented!("Page unmapping is not yet implemented!");
+    // same as `unmap_page()` but the TLB entry is left for the caller to flush
+    fn unmap_page_no_flush(&mut self, page: &Page) -> Result<Frame, MemoryError> {
+        let entry = self.p1_entry_mut(page)?;
+        let frame = entry.pointed_frame().ok_or(MemoryError::UnmappingUnusedTableEntry)?;
 
-        // ASM is going to be needed to invalidate tlb entries
-        // let x: u64;
-        // unsafe {
-        //     asm!(
-        //         "mov {0}, 42",
-        //         out(reg) x,
-        //     );
-        // }
+        entry.set_unused();
+        Ok(frame)
+    }
+
+    /*
+     * Changes the flags of an already mapped page, keeping the frame it points to.
+     * PRESENT is always added to `flags`.
+     */
+    pub fn update_flags(&mut self, page: Page, flags: EntryFlags) -> Result<(), MemoryError> {
+        let entry = self.p1_entry_mut(&page)?;
+        let frame = entry.pointed_frame().ok_or(MemoryError::UnmappingUnusedTableEntry)?;
+
+        entry.set(frame, flags | EntryFlags::PRESENT);
+        CR3::invalidate_entry(page.addr());
+
+        Ok(())
+    }
+
+    // walks the tables down to the P1 entry of `page`, failing if a table is missing or a huge page is involved
+    fn p1_entry_mut(&mut self, page: &Page) -> Result<&mut Entry, MemoryError> {
+        let huge_flags = EntryFlags::PRESENT | EntryFlags::HUGE_PAGE;
+
+        let p3 = self.p4_mut().next_table_mut(page.p4_index()).ok_or(MemoryError::UnmappingUnusedTableEntry)?;
+        if p3.entries[page.p3_index()].flags().contains(huge_flags) {
+            return Err(MemoryError::CannotUnmapHugePage);
+        }
+
+        let p2 = p3.next_table_mut(page.p3_index()).ok_or(MemoryError::UnmappingUnusedTableEntry)?;
+        if p2.entries[page.p2_index()].flags().contains(huge_flags) {
+            return Err(MemoryError::CannotUnmapHugePage);
+        }
+
+        let p1 = p2.next_table_mut(page.p2_index()).ok_or(MemoryError::UnmappingUnusedTableEntry)?;
+        Ok(&mut p1.entries[page.p1_index()])
+    }
+
+    /*
+     * Identity maps every frame that contains at least one byte of [start, start + len), meant for MMIO regions.
+     * NO_CACHE is always added to `flags`. If mapping any page fails (it's already mapped, or there are no frames
+     * left for the page tables), the pages mapped by this call are unmapped again before returning the error,
+     * so the range is either fully mapped or not at all. The page tables created on the way are kept.
+     */
+    pub fn identity_map_range<A: FrameAllocator>(&mut self, start: PhysicalAddress, len: usize, frame_allocator: &mut A, flags: EntryFlags) -> Result<(), MemoryError> {
+        let last = len.checked_sub(1).and_then(|l| start.checked_add(l)).ok_or(MemoryError::InvalidRange)?;
+
+        // the identity mapped pages must be in the lower half of the virtual address space
+        if last >= NON_CANONICAL.start {
+            return Err(MemoryError::InvalidRange);
+        }
+
+        let frames = Frame::range_inclusive(start, last);
+        let first_page = Page::from_virt_addr(Frame::from_phy_addr(start).addr());
+
+        for (mapped, frame) in frames.enumerate() {
+            let page = Page::from_virt_addr(frame.addr());
+            if let Err(err) = self.map_page_to_frame(page, frame, frame_allocator, flags | EntryFlags::NO_CACHE) {
+                self.unmap_range(first_page, mapped, frame_allocator, false)?;
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Unmaps `count` pages starting at `start` and returns how many of them were actually mapped.
+     * Unmapped pages in the range are skipped instead of being treated as errors.
+     * If `deallocate_frames` is set, the frames that were mapped are given back to `frame_allocator`.
+     *
+     * Ranges bigger than `INVALIDATE_ALL_THRESHOLD` pages are flushed with a single full TLB flush at the end.
+     */
+    pub fn unmap_range<A: FrameAllocator>(&mut self, start: Page, count: usize, frame_allocator: &mut A, deallocate_frames: bool) -> Result<usize, MemoryError> {
+        let flush_all = count > INVALIDATE_ALL_THRESHOLD;
+        let mut unmapped = 0;
+        let mut result = Ok(());
+
+        for page in PageRange::new(start, count) {
+            match self.unmap_page_no_flush(&page) {
+                Ok(frame) => {
+                    if !flush_all {
+                        CR3::invalidate_entry(page.addr());
+                    }
+
+                    if deallocate_frames {
+                        frame_allocator.deallocate_frame(frame);
+                    }
+
+                    unmapped += 1;
+                }
+                Err(MemoryError::UnmappingUnusedTableEntry) => continue,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+
+        // the pages unmapped before an error must be flushed as well
+        if flush_all && unmapped > 0 {
+            CR3::invalidate_all();
+        }
+
+        result.map(|_| unmapped)
     }
 
     /*
@@ -174,6 +439,55 @@ impl Paging {
 
         Some(frame.addr() + offset)
     }
+
+    /*
+     * Translates the range [start, start + len) one page at a time.
+     * The first item is the translation of `start` and every other one is the translation of the start of a page.
+     * Empty ranges, ranges that overflow and ranges that touch the non canonical addrs are invalid.
+     */
+    pub fn translate_range(&self, start: VirtualAddress, len: usize) -> Result<impl Iterator<Item = Option<PhysicalAddress>> + '_, MemoryError> {
+        let last = len.checked_sub(1).and_then(|l| start.checked_add(l)).ok_or(MemoryError::InvalidRange)?;
+
+        // both ends being canonical is not enough, the range could still go from the lower half to the higher one
+        if !is_canonical(start) || !is_canonical(last) || (start < NON_CANONICAL.start) != (last < NON_CANONICAL.start) {
+            return Err(MemoryError::InvalidRange);
+        }
+
+        Ok(Page::range_inclusive(start, last).map(move |page| self.translate(page.addr().max(start))))
+    }
+
+    // an empty range is always considered mapped
+    pub fn is_range_mapped(&self, start: VirtualAddress, len: usize) -> bool {
+        match self.translate_range(start, len) {
+            Ok(mut translations) => translations.all(|addr| addr.is_some()),
+            Err(_) => len == 0,
+        }
+    }
+
+    /*
+     * Temporarily maps `frame` to a reserved virtual page and runs `f` with a pointer to the start of it.
+     * The page is unmapped when `f` returns, but the frame is left untouched (it's not deallocated).
+     * The page tables created for the reserved page are kept around to be reused by the next call.
+     *
+     * This can't be nested, a call from within `f` returns `MemoryError::TemporaryPageInUse`.
+     */
+    pub fn with_mapped_frame<A: FrameAllocator, R>(&mut self, frame: Frame, frame_allocator: &mut A, f: impl FnOnce(*mut u8) -> R) -> Result<R, MemoryError> {
+        if self.translate(TEMPORARY_PAGE_ADDR).is_some() {
+            return Err(MemoryError::TemporaryPageInUse);
+        }
+
+        // NO_EXECUTE is a reserved bit (page fault on access) unless it is enabled in EFER
+        let mut flags = EntryFlags::WRITABLE;
+        if efer::get().contains(Efer::NO_EXECUTE_ENABLE) {
+            flags |= EntryFlags::NO_EXECUTE;
+        }
+
+        self.map_page_to_frame(Page::from_virt_addr(TEMPORARY_PAGE_ADDR), frame, frame_allocator, flags)?;
+        let result = f(TEMPORARY_PAGE_ADDR as *mut u8);
+        self.unmap_page(Page::from_virt_addr(TEMPORARY_PAGE_ADDR))?;
+
+        Ok(result)
+    }
 }
 
 pub fn test_paging<A: FrameAllocator>(frame_allocator: &mut A) {
@@ -188,7 +502,256 @@ pub fn test_paging<A: FrameAllocator>(frame_allocator: &mut A) {
         page_table.translate(virt_addr),
         frame
     );
-    page_table.map_page_to_frame(page, frame, frame_allocator, EntryFlags::empty());
+    page_table.map_page_to_frame(page, frame, frame_allocator, EntryFlags::empty()).expect("Could not map the test page.");
     println!("Some = {:?}", page_table.translate(virt_addr));
     println!("next free frame: {:?}", frame_allocator.allocate_frame());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::FrameAllocatorStats;
+
+    #[repr(C, align(4096))]
+    struct TestFrame([u8; PAGE_SIZE]);
+
+    // hands out zeroed host memory as frames (see `Table::next_table_addr()`)
+    #[derive(Default)]
+    struct TestAllocator {
+        frames: Vec<Box<TestFrame>>,
+        max_frames: Option<usize>,
+    }
+
+    impl FrameAllocator for TestAllocator {
+        fn allocate_frame(&mut self) -> Option<Frame> {
+            if self.max_frames.is_some_and(|max| self.frames.len() >= max) {
+                return None;
+            }
+
+            let frame = Box::new(TestFrame([0; PAGE_SIZE]));
+            let addr = &*frame as *const TestFrame as PhysicalAddress;
+            self.frames.push(frame);
+            Some(Frame::from_phy_addr(addr))
+        }
+
+        fn deallocate_frame(&mut self, _frame: Frame) {}
+
+        fn stats(&self) -> FrameAllocatorStats {
+            FrameAllocatorStats { total_frames: self.frames.len(), used_frames: self.frames.len(), free_frames: 0 }
+        }
+
+        fn allocate_contiguous(&mut self, _count: usize) -> Result<Frame, MemoryError> {
+            Err(MemoryError::NotEnoughPhyMemory)
+        }
+    }
+
+    fn host_paging(allocator: &mut TestAllocator) -> Paging {
+        let p4 = allocator.allocate_frame().unwrap();
+        unsafe { Paging::from_p4(NonNull::new(p4.addr() as *mut _).unwrap()) }
+    }
+
+    fn page_indexes(start_addr: VirtualAddress, end_addr: VirtualAddress) -> Vec<usize> {
+        Page::range_inclusive(start_addr, end_addr).map(|page| page.0).collect()
+    }
+
+    #[test]
+    fn page_ranges_include_partial_pages() {
+        assert_eq!(page_indexes(0x1000, 0x1fff), [1]);
+        assert_eq!(page_indexes(0x1fff, 0x3000), [1, 2, 3]);
+        assert_eq!(page_indexes(0x1800, 0x27ff), [1, 2]);
+
+        // the higher half works the same way
+        let base = 0xffff_8000_0000_0000;
+        assert_eq!(page_indexes(base + 0x10, base + PAGE_SIZE).len(), 2);
+    }
+
+    #[test]
+    fn page_ranges_ending_before_the_start_are_empty() {
+        assert!(page_indexes(0x3000, 0x1000).is_empty());
+    }
+
+    #[test]
+    fn pages_are_ordered_like_their_addresses() {
+        let addrs = [0x0, 0xfff, 0x1000, 0x7fff_ffff_f000, 0xffff_8000_0000_0000, 0xffff_ffff_ffff_f000];
+        let pages: Vec<Page> = addrs.iter().map(|&addr| Page::from_virt_addr(addr)).collect();
+
+        assert!(pages[0] == pages[1]);
+        assert!(pages[1..].windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(pages.iter().max().map(|page| page.0), Some(usize::MAX / PAGE_SIZE));
+    }
+
+    #[test]
+    fn containing_ranges_exclude_the_end() {
+        let indexes = |start, end| Page::containing_range(start, end).map(|page| page.0).collect::<Vec<_>>();
+
+        assert_eq!(indexes(0x1000, 0x2000), [1]);
+        assert_eq!(indexes(0x1000, 0x2001), [1, 2]);
+        assert_eq!(indexes(0x1fff, 0x2001), [1, 2]);
+        assert_eq!(indexes(0x1800, 0x1801), [1]);
+
+        // empty ranges
+        assert!(indexes(0x1800, 0x1800).is_empty());
+        assert!(indexes(0x3000, 0x1000).is_empty());
+    }
+
+    #[test]
+    fn pages_print_their_addr() {
+        assert_eq!(format!("{:?}", Page(2)), "Page(idx=2 addr=0x2000)");
+        assert_eq!(format!("{}", Page::from_virt_addr(0xffff_ffff_ffff_f123)), "Page(idx=4503599627370495 addr=0xfffffffffffff000)");
+    }
+
+    #[test]
+    fn page_ranges_know_their_length() {
+        // the last page of the lower half, and the last page of all
+        assert_eq!(Page::range_inclusive(0x7fff_ffff_f000, 0x7fff_ffff_ffff).len(), 1);
+        assert_eq!(page_indexes(0xffff_ffff_ffff_f000, usize::MAX), [usize::MAX / PAGE_SIZE]);
+
+        let mut range = PageRange::new(Page(7), 3);
+        assert_eq!(range.len(), 3);
+        assert_eq!(range.next().map(|page| page.0), Some(7));
+        assert_eq!(range.map(|page| page.0).collect::<Vec<_>>(), [8, 9]);
+        assert_eq!(Page::range_inclusive(0x3000, 0x1000).len(), 0);
+    }
+
+    #[test]
+    fn maps_2mib_pages() {
+        let mut allocator = TestAllocator::default();
+        let mut paging = host_paging(&mut allocator);
+
+        // the frame is never accessed, so any (aligned) addr works
+        let virt_addr = 0x4000_0000;
+        let phy_addr = 0x60_0000;
+        let page = Page::from_virt_addr(virt_addr);
+        paging.map_huge_page(page, Frame::from_phy_addr(phy_addr), &mut allocator, EntryFlags::WRITABLE, HugePageSize::Size2MiB).unwrap();
+
+        assert_eq!(paging.translate(virt_addr), Some(phy_addr));
+        assert_eq!(paging.translate(virt_addr + 0x1234), Some(phy_addr + 0x1234));
+        assert_eq!(paging.translate(virt_addr + 0x1f_ffff), Some(phy_addr + 0x1f_ffff));
+        assert_eq!(paging.translate(virt_addr + 0x20_0000), None);
+        assert_eq!(paging.translate(virt_addr - 1), None);
+    }
+
+    #[test]
+    fn refuses_used_huge_page_slots_without_creating_tables() {
+        let mut allocator = TestAllocator::default();
+        let mut paging = host_paging(&mut allocator);
+
+        let virt_addr = 0x4000_0000;
+        let frame = || Frame::from_phy_addr(0x4000_0000);
+        paging.map_huge_page(Page::from_virt_addr(virt_addr), frame(), &mut allocator, EntryFlags::empty(), HugePageSize::Size2MiB).unwrap();
+        let tables = allocator.frames.len();
+
+        // the same slot, and a 1GiB page over the P2 table that holds it
+        for size in [HugePageSize::Size2MiB, HugePageSize::Size1GiB] {
+            let res = paging.map_huge_page(Page::from_virt_addr(virt_addr), frame(), &mut allocator, EntryFlags::empty(), size);
+            assert!(matches!(res, Err(MemoryError::MappingUsedTableEntry)));
+        }
+
+        // a 1GiB page, and a 2MiB page inside of it
+        let virt_addr = 0x8000_0000;
+        paging.map_huge_page(Page::from_virt_addr(virt_addr), frame(), &mut allocator, EntryFlags::empty(), HugePageSize::Size1GiB).unwrap();
+        let res = paging.map_huge_page(Page::from_virt_addr(virt_addr + 0x20_0000), frame(), &mut allocator, EntryFlags::empty(), HugePageSize::Size2MiB);
+        assert!(matches!(res, Err(MemoryError::MappingUsedTableEntry)));
+        assert_eq!(paging.translate(virt_addr + 0x20_1000), Some(0x4020_1000));
+
+        let res = paging.map_huge_page(Page::from_virt_addr(0x1000), frame(), &mut allocator, EntryFlags::empty(), HugePageSize::Size2MiB);
+        assert!(matches!(res, Err(MemoryError::HugePageNotAligned)));
+        assert_eq!(allocator.frames.len(), tables);
+    }
+
+    #[test]
+    fn translates_ranges() {
+        let mut allocator = TestAllocator::default();
+        let mut paging = host_paging(&mut allocator);
+
+        // two pages mapped out of order, followed by a gap
+        let base = 0x40_0000;
+        paging.map_page_to_frame(Page::from_virt_addr(base), Frame(0x20), &mut allocator, EntryFlags::empty()).unwrap();
+        paging.map_page_to_frame(Page::from_virt_addr(base + PAGE_SIZE), Frame(0x10), &mut allocator, EntryFlags::empty()).unwrap();
+
+        let translations: Vec<_> = paging.translate_range(base + 0x10, 3 * PAGE_SIZE - 0x10).unwrap().collect();
+        assert_eq!(translations, [Some(0x20010), Some(0x10000), None]);
+
+        assert!(paging.is_range_mapped(base + 0x10, 2 * PAGE_SIZE - 0x10));
+        assert!(!paging.is_range_mapped(base + 0x10, 2 * PAGE_SIZE - 0xf));
+        assert!(!paging.is_range_mapped(base - 1, 2));
+        assert!(paging.is_range_mapped(base - 1, 0));
+    }
+
+    #[test]
+    fn rejects_invalid_ranges() {
+        let allocator = &mut TestAllocator::default();
+        let paging = host_paging(allocator);
+        let invalid = |start, len| matches!(paging.translate_range(start, len), Err(MemoryError::InvalidRange));
+
+        // empty and overflowing
+        assert!(invalid(0x1000, 0));
+        assert!(invalid(usize::MAX, 2));
+
+        // ending in, starting in and going over the non canonical addrs
+        assert!(invalid(0x7fff_ffff_f000, 0x2000));
+        assert!(invalid(0x0000_8000_0000_0000, 0x1000));
+        assert!(invalid(0xffff_7fff_ffff_f000, 0x2000));
+        assert!(invalid(0x7fff_ffff_f000, 0xffff_8000_0000_1000 - 0x7fff_ffff_f000));
+        assert!(!paging.is_range_mapped(0x7fff_ffff_f000, 0x2000));
+
+        // the last page of each half is fine
+        assert!(!invalid(0x7fff_ffff_f000, 0x1000));
+        assert!(!invalid(0xffff_ffff_ffff_f000, 0x1000));
+    }
+
+    #[test]
+    fn identity_maps_ranges() {
+        let mut allocator = TestAllocator::default();
+        let mut paging = host_paging(&mut allocator);
+
+        paging.identity_map_range(0x1800, 0x2000, &mut allocator, EntryFlags::WRITABLE).unwrap();
+        for addr in [0x1000, 0x1800, 0x37ff] {
+            assert_eq!(paging.translate(addr), Some(addr));
+        }
+        assert!(paging.p1_entry_mut(&Page::from_virt_addr(0x2000)).unwrap().flags().contains(EntryFlags::NO_CACHE));
+        assert!(paging.p1_entry_mut(&Page::from_virt_addr(0x2000)).unwrap().flags().contains(EntryFlags::NO_CACHE));
+
+        assert!(matches!(paging.identity_map_range(0x1000, 0, &mut allocator, EntryFlags::empty()), Err(MemoryError::InvalidRange)));
+        assert!(matches!(paging.identity_map_range(0x7fff_ffff_f000, 0x2000, &mut allocator, EntryFlags::empty()), Err(MemoryError::InvalidRange)));
+    }
+
+    #[test]
+    fn identity_maps_nothing_if_a_page_is_used() {
+        let mut allocator = TestAllocator::default();
+        let mut paging = host_paging(&mut allocator);
+
+        // the last page of the range and a 2MiB page right after it
+        paging.map_page_to_frame(Page::from_virt_addr(0x3000), Frame(0x42), &mut allocator, EntryFlags::empty()).unwrap();
+        paging.map_huge_page(Page::from_virt_addr(0x20_0000), Frame(0x200), &mut allocator, EntryFlags::empty(), HugePageSize::Size2MiB).unwrap();
+
+        let res = paging.identity_map_range(0x1000, 0x3000, &mut allocator, EntryFlags::empty());
+        assert!(matches!(res, Err(MemoryError::MappingUsedTableEntry)));
+        assert_eq!(paging.translate(0x1000), None);
+        assert_eq!(paging.translate(0x2000), None);
+        assert_eq!(paging.translate(0x3000), Some(0x42000));
+
+        let res = paging.identity_map_range(0x1f_e000, 0x3000, &mut allocator, EntryFlags::empty());
+        assert!(matches!(res, Err(MemoryError::MappingUsedTableEntry)));
+        assert_eq!(paging.translate(0x1f_e000), None);
+        assert_eq!(paging.translate(0x1f_f000), None);
+    }
+
+    #[test]
+    fn identity_maps_nothing_without_frames_for_the_tables() {
+        // the P4, P3, P2 and the first P1 table
+        let mut allocator = TestAllocator { max_frames: Some(4), ..Default::default() };
+        let mut paging = host_paging(&mut allocator);
+
+        // the second page needs a new P1 table
+        let res = paging.identity_map_range(0x1f_f000, 0x2000, &mut allocator, EntryFlags::empty());
+        assert!(matches!(res, Err(MemoryError::NotEnoughPhyMemory)));
+        assert_eq!(paging.translate(0x1f_f000), None);
+
+        // the P1 table created for the first page is kept
+        assert_eq!(allocator.frames.len(), 4);
+        paging.map_page_to_frame(Page::from_virt_addr(0x1f_f000), Frame(1), &mut allocator, EntryFlags::empty()).unwrap();
+
+        assert!(matches!(paging.map_page(Page::from_virt_addr(0x1f_e000), &mut allocator, EntryFlags::empty()), Err(MemoryError::NotEnoughPhyMemory)));
+    }
+}
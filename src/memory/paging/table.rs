@@ -1,5 +1,5 @@
 use super::{entry::{Entry, EntryFlags}, ENTRY_COUNT};
-use crate::memory::{FrameAllocator, PAGE_SIZE};
+use crate::{cpu::barrier, memory::{fast_mem::fast_zero, FrameAllocator, MemoryError, PAGE_SIZE}};
 use core::marker::PhantomData;
 
 /*
@@ -59,9 +59,8 @@ pub struct Table<L: TableLevel> {
 
 impl<L: TableLevel> Table<L> {
     fn set_unused(&mut self) {
-        for entry in &mut self.entries {
-            entry.set_unused();
-        }
+        // an unused entry is all zeros, so the whole table can be cleared in one go
+        unsafe { fast_zero(self.entries.as_mut_ptr().cast(), size_of::<[Entry; ENTRY_COUNT]>()) };
     }
 }
 
@@ -72,6 +71,11 @@ impl<L: HierarchicalLevel> Table<L> {
 
         let entry_flags = self.entries[table_index].flags();
         if entry_flags.contains(EntryFlags::PRESENT) && !entry_flags.contains(EntryFlags::HUGE_PAGE) {
+            // the host tests don't have a recursive mapping, so their tables are accessed through the physical addr
+            if cfg!(test) {
+                return self.entries[table_index].phy_addr();
+            }
+
             let res = self as *const _ as usize;
             return Some((res << 9) | (table_index << 12)); // see comment at the top
         }
@@ -87,17 +91,21 @@ impl<L: HierarchicalLevel> Table<L> {
         Some(unsafe { &mut *(self.next_table_addr(table_index)? as *mut _) })
     }
 
-    pub fn create_next_table<A: FrameAllocator>(&mut self, table_index: usize, frame_allocator: &mut A) -> &mut Table<L::NextLevel> {
+    /*
+     * Returns the table pointed to by the entry at `table_index`, creating it if it doesn't exist yet.
+     * Fails if the entry holds a huge page or if there is no frame left for the new table.
+     */
+    pub fn create_next_table<A: FrameAllocator>(&mut self, table_index: usize, frame_allocator: &mut A) -> Result<&mut Table<L::NextLevel>, MemoryError> {
         // check if page table is already allocated
         if self.next_table(table_index).is_none() {
             // this might happen if the page we are trying to allocate might
             // involve huge pages previously allocatted
             if self.entries[table_index].flags().contains(EntryFlags::HUGE_PAGE) {
-                unimplemented!("Cannot allocate pages with HUGE_PAGE flag set yet!");
+                return Err(MemoryError::MappingUsedTableEntry);
             }
 
             // page table is not yet created so allocate a new frame to hold the new page table
-            let frame = frame_allocator.allocate_frame().expect("Out of memory. Could not allocate new frame.");
+            let frame = frame_allocator.allocate_frame().ok_or(MemoryError::NotEnoughPhyMemory)?;
 
             // physical address needs to be page aligned
             assert!(frame.addr() % PAGE_SIZE == 0);
@@ -105,11 +113,15 @@ impl<L: HierarchicalLevel> Table<L> {
             // set the new entry
             self.entries[table_index].set(frame, EntryFlags::PRESENT | EntryFlags::WRITABLE);
 
+            // the new table is accessed through the recursive mapping (a different address), so the entry
+            // must be written before the compiler emits any access to the table itself
+            barrier::compiler_fence();
+
             // this unwrap() should never fail as we just set the entry above
             self.next_table_mut(table_index).unwrap().set_unused();
         }
 
         // at this point, we have a valid entry at `table_index` so this unwrap() is fine
-        self.next_table_mut(table_index).unwrap()
+        Ok(self.next_table_mut(table_index).unwrap())
     }
 }
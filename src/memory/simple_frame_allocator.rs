@@ -1,11 +1,13 @@
 use crate::multiboot2::memory_map::{MemoryMapEntry, MemoryMapEntryType};
-use super::{Frame, FrameAllocator};
+use super::{Frame, FrameAllocator, FrameAllocatorStats, MemoryError};
 
 pub struct SimpleFrameAllocator<'a> {
     // areas and the respective frames
     areas: &'a [MemoryMapEntry],
     current_area: usize,
     next_frame: Frame,
+    allocated_frames: usize,
+    last_allocated: Option<(Frame, usize)>, // the last frame handed out and its area, the only one that can be given back
 
     // memory ranges that we need to avoid using so we don't override important memory
     k_start: Frame,
@@ -20,6 +22,8 @@ impl<'a> SimpleFrameAllocator<'a> {
             areas,
             current_area: 0,
             next_frame: Frame(0x0),
+            allocated_frames: 0,
+            last_allocated: None,
 
             k_start: Frame::from_phy_addr(k_start),
             k_end: Frame::from_phy_addr(k_end),
@@ -48,8 +52,8 @@ impl<'a> SimpleFrameAllocator<'a> {
         let curr_area = &self.areas[self.current_area];
         let fr_after_last_in_curr_area= Frame::from_phy_addr((curr_area.base_addr + curr_area.length) as _);
 
-        // check if the next frame is pointing outside the current area
-        if self.next_frame == fr_after_last_in_curr_area {
+        // check if the next frame would be outside the current area
+        if Frame(self.next_frame.0 + 1) >= fr_after_last_in_curr_area {
             self.current_area += 1;
 
             // get to the next area with available ram
@@ -72,6 +76,32 @@ impl<'a> SimpleFrameAllocator<'a> {
         Some(self.next_frame)
     }
 
+    // returns the frames in an area as [first, last) frame indexes (same boundaries used by `get_next_frame()`)
+    fn area_frames(area: &MemoryMapEntry) -> (usize, usize) {
+        let first = Frame::from_phy_addr(area.base_addr as usize);
+        let after_last = Frame::from_phy_addr((area.base_addr + area.length) as usize);
+        (first.0, after_last.0)
+    }
+
+    // amount of frames in available areas that are never handed out because they hold the kernel or the mb2 info
+    fn reserved_frames(&self) -> usize {
+        let available_areas = self.areas.iter().filter(|area| area.entry_type() == MemoryMapEntryType::AvailableRAM);
+        let mut reserved = 0;
+
+        for area in available_areas {
+            let (first, after_last) = Self::area_frames(area);
+
+            for (start, end) in [(self.k_start, self.k_end), (self.mb_start, self.mb_end)] {
+                // the reserved ranges are inclusive
+                let overlap_start = first.max(start.0);
+                let overlap_end = after_last.min(end.0 + 1);
+                reserved += overlap_end.saturating_sub(overlap_start);
+            }
+        }
+
+        reserved
+    }
+
     fn get_next_free_frame(&mut self) -> Option<Frame> {
         let mut fr = self.get_next_frame()?;
 
@@ -85,15 +115,194 @@ impl<'a> SimpleFrameAllocator<'a> {
 
 impl<'a> FrameAllocator for SimpleFrameAllocator<'a> {
     fn allocate_frame(&mut self) -> Option<Frame> {
-        let ret = Some(self.next_frame);
+        let ret = (self.next_frame, self.current_area);
         self.get_next_free_frame()?;
+        self.allocated_frames += 1;
+        self.last_allocated = Some(ret);
 
-        ret
+        Some(ret.0)
     }
 
+    /*
+     * There is no record of which frames are free, so only the last allocated frame can be given back (by rewinding the scan).
+     * Any other frame is leaked. This is enough to undo an allocation that failed right after getting its frame.
+     */
     fn deallocate_frame(&mut self, frame: Frame) {
-        // for this, we will need some way to store a record of which frames are free and which ones are not
-        // this may even require allocation (just a guess)
-        unimplemented!();
+        if let Some((last, area)) = self.last_allocated {
+            if last == frame {
+                self.next_frame = last;
+                self.current_area = area;
+                self.allocated_frames -= 1;
+                self.last_allocated = None;
+            }
+        }
+    }
+
+    /*
+     * Scans forward from the next free frame for a run of `count` frames without any reserved frame or area gap in between.
+     * There is no record of free frames, so the frames skipped before the run are lost for good (they count as used).
+     * For the same reason, the run can't be given back (see `deallocate_frame()`).
+     * If there is no such run, nothing is allocated.
+     */
+    fn allocate_contiguous(&mut self, count: usize) -> Result<Frame, MemoryError> {
+        assert!(count > 0);
+        let (saved_area, saved_frame) = (self.current_area, self.next_frame);
+
+        let mut start = self.next_frame;
+        let mut end = self.next_frame; // last frame in the run
+        let mut taken = 1; // every free frame up to `end`
+        while end.0 - start.0 + 1 < count {
+            let Some(fr) = self.get_next_free_frame() else {
+                self.current_area = saved_area;
+                self.next_frame = saved_frame;
+                return Err(MemoryError::NotEnoughPhyMemory);
+            };
+
+            // areas may be physically adjacent, so only the addresses matter
+            if fr.0 != end.0 + 1 {
+                start = fr;
+            }
+            end = fr;
+            taken += 1;
+        }
+
+        // move past the run, just like `allocate_frame()` does
+        if self.get_next_free_frame().is_none() {
+            self.current_area = saved_area;
+            self.next_frame = saved_frame;
+            return Err(MemoryError::NotEnoughPhyMemory);
+        }
+
+        self.allocated_frames += taken;
+        self.last_allocated = None; // rewinding to an older frame would hand out the run again
+        Ok(start)
+    }
+
+    /*
+     * The used frames are the ones handed out so far. The free ones are whatever the linear scan can still reach,
+     * which is every frame in the available areas that was not allocated yet and isn't reserved.
+     */
+    fn stats(&self) -> FrameAllocatorStats {
+        let total_frames = self.areas.iter()
+            .filter(|area| area.entry_type() == MemoryMapEntryType::AvailableRAM)
+            .map(|area| {
+                let (first, after_last) = Self::area_frames(area);
+                after_last - first
+            })
+            .sum();
+
+        FrameAllocatorStats {
+            total_frames,
+            used_frames: self.allocated_frames,
+            free_frames: total_frames.saturating_sub(self.allocated_frames + self.reserved_frames()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::PAGE_SIZE;
+    use crate::multiboot2::{memory_map::tests::payload, tests::boot_info, MbBootInfo, TagType};
+
+    const AVAILABLE: u32 = 1;
+    const RESERVED: u32 = 2;
+
+    fn areas() -> [MemoryMapEntry; 3] {
+        [
+            MemoryMapEntry::new(0x0, 0x10000, AVAILABLE),          // 16 frames
+            MemoryMapEntry::new(0x10000, 0x10000, RESERVED),
+            MemoryMapEntry::new(0x100000, 0x20000, AVAILABLE),     // 32 frames
+        ]
+    }
+
+    #[test]
+    fn counts_allocated_and_reserved_frames() {
+        let areas = areas();
+
+        // the kernel takes 5 frames at the start of the second available area and the mb2 info takes 1 in the first
+        let mut allocator = SimpleFrameAllocator::new(&areas, 0x100000, 0x104fff, 0x2000, 0x2fff).unwrap();
+
+        let stats = allocator.stats();
+        assert_eq!(stats.total_frames, 48);
+        assert_eq!(stats.used_frames, 0);
+        assert_eq!(stats.free_frames, 48 - 6);
+
+        let frames: Vec<Frame> = (0..20).map(|_| allocator.allocate_frame().unwrap()).collect();
+        assert!(frames.iter().all(|frame| frame.0 != 2 && !(0x100..=0x104).contains(&frame.0) && !(0x10..0x100).contains(&frame.0)));
+
+        let stats = allocator.stats();
+        assert_eq!(stats.used_frames, 20);
+        assert_eq!(stats.free_frames, 48 - 6 - 20);
+        assert_eq!(stats.total_frames * PAGE_SIZE, 0x30000);
+    }
+
+    #[test]
+    fn allocates_contiguous_frames() {
+        let areas = areas();
+        let mut allocator = SimpleFrameAllocator::new(&areas, 0x100000, 0x104fff, 0x2000, 0x2fff).unwrap();
+        allocator.allocate_frame().unwrap();
+
+        // frame 1 is followed by the mb2 info, and the first area is too small after it
+        let first = allocator.allocate_contiguous(16).unwrap();
+        assert_eq!(first.addr(), 0x105000);
+        assert_eq!(allocator.allocate_frame().unwrap().addr(), 0x105000 + 16 * PAGE_SIZE);
+
+        // everything up to the end of the run is gone
+        let stats = allocator.stats();
+        assert_eq!(stats.used_frames, 1 + 14 + 16 + 1);
+        assert_eq!(stats.free_frames, 48 - 6 - stats.used_frames);
+
+        // a run that doesn't exist doesn't allocate anything
+        assert!(matches!(allocator.allocate_contiguous(16), Err(MemoryError::NotEnoughPhyMemory)));
+        assert_eq!(allocator.stats().used_frames, stats.used_frames);
+        assert_eq!(allocator.allocate_frame().unwrap().addr(), 0x105000 + 17 * PAGE_SIZE);
+    }
+
+    #[test]
+    fn only_gives_back_the_last_frame() {
+        let areas = areas();
+        let mut allocator = SimpleFrameAllocator::new(&areas, 0x100000, 0x104fff, 0x2000, 0x2fff).unwrap();
+
+        let first = allocator.allocate_frame().unwrap();
+        let last = allocator.allocate_frame().unwrap();
+        assert_eq!(last.0, 1);
+        allocator.deallocate_frame(last);
+        assert_eq!(allocator.stats().used_frames, 1);
+        assert_eq!(allocator.allocate_frame(), Some(last));
+
+        // frame 2 holds the mb2 info
+        assert_eq!(allocator.allocate_frame(), Some(Frame(3)));
+
+        // older frames are leaked
+        allocator.deallocate_frame(first);
+        assert_eq!(allocator.stats().used_frames, 3);
+        assert_eq!(allocator.allocate_frame(), Some(Frame(4)));
+
+        // and so is a contiguous run
+        let run = allocator.allocate_contiguous(2).unwrap();
+        let used = allocator.stats().used_frames;
+        allocator.deallocate_contiguous(run, 2);
+        assert_eq!(allocator.stats().used_frames, used);
+        assert_eq!(allocator.allocate_frame(), Some(Frame(run.0 + 2)));
+    }
+
+    #[test]
+    fn counts_the_same_memory_as_the_boot_info() {
+        // only the whole frames of the available areas count, so unaligned bases and ends are cut
+        let map = [(AVAILABLE, 0x0, 0x9fc00), (RESERVED, 0x9fc00, 0x400), (AVAILABLE, 0x100800, 0x100000), (AVAILABLE, 0x7fe0000, 0x1fff0)];
+        let areas = map.map(|(entry_type, base_addr, length)| MemoryMapEntry::new(base_addr, length, entry_type));
+        let allocator = SimpleFrameAllocator::new(&areas, 0x200000, 0x2fffff, 0x300000, 0x300fff).unwrap();
+
+        let payload = payload(24, &map, 0);
+        let buf = boot_info(&[(TagType::MemoryMap as u32, &payload)]);
+        let mb_info = unsafe { MbBootInfo::new(buf.as_ptr().cast()) }.unwrap();
+
+        assert_eq!(mb_info.total_usable_memory(), 0x9f000 + 0x100000 + 0x1f000);
+        assert_eq!(mb_info.total_usable_memory(), (allocator.stats().total_frames * PAGE_SIZE) as u64);
+        assert_eq!(mb_info.total_memory(), 0x9fc00 + 0x400 + 0x100000 + 0x1fff0);
+
+        assert_eq!(areas[2].aligned_length(), 0x100000);
+        assert_eq!(areas[3].aligned_length(), 0x1f000);
     }
 }
@@ -0,0 +1,149 @@
+// https://wiki.osdev.org/RSDP
+use super::{tag_trait::MbTag, MbTagHeader, TagType};
+use crate::memory::PhysicalAddress;
+use core::{ptr::addr_of, slice::from_raw_parts};
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+const RSDP_V1_LEN: usize = 20; // the first checksum only covers the ACPI 1.0 fields
+const RSDP_V2_LEN: usize = 36;
+
+/*
+ * This tag holds a copy of the ACPI 2.0+ RSDP (the fields after the header are the RSDP itself).
+ */
+#[repr(C)]
+pub(crate) struct AcpiNewRsdp {
+    header: MbTagHeader,
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_address: u32,
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    reserved: [u8; 3],
+}
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub(crate) enum AcpiRsdpError {
+    BadSignature,
+    BadLength,
+    BadChecksum,
+}
+
+impl AcpiNewRsdp {
+    // the RSDP bytes, starting at the signature
+    fn bytes(&self, len: usize) -> &[u8] {
+        unsafe { from_raw_parts(addr_of!(self.signature).cast::<u8>(), len) }
+    }
+
+    /*
+     * Checks the signature and both checksums (every covered byte must add up to 0, ignoring overflow).
+     * The RSDP length is also checked against the tag size so that a bad length can't make us read past the tag.
+     */
+    pub(crate) fn validate(&self) -> Result<(), AcpiRsdpError> {
+        if &self.signature != RSDP_SIGNATURE {
+            return Err(AcpiRsdpError::BadSignature);
+        }
+
+        let max_len = (self.header.size as usize).saturating_sub(size_of::<MbTagHeader>());
+        let len = self.length as usize;
+        if len < RSDP_V2_LEN || len > max_len {
+            return Err(AcpiRsdpError::BadLength);
+        }
+
+        let sum = |bytes: &[u8]| bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+        if sum(self.bytes(RSDP_V1_LEN)) != 0 || sum(self.bytes(len)) != 0 {
+            return Err(AcpiRsdpError::BadChecksum);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    pub(crate) fn oem_id(&self) -> &[u8; 6] {
+        &self.oem_id
+    }
+
+    // the physical address of the XSDT, only returned if the RSDP is valid
+    pub(crate) fn xsdt_address(&self) -> Result<PhysicalAddress, AcpiRsdpError> {
+        self.validate()?;
+        Ok(self.xsdt_address as PhysicalAddress)
+    }
+}
+
+impl MbTag for AcpiNewRsdp {
+    const TAG_TYPE: TagType = TagType::AcpiNewRsdp;
+
+    fn dst_size(_base_tag: &MbTagHeader) -> Self::Metadata {
+        ()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_rsdp() -> AcpiNewRsdp {
+        let mut rsdp = AcpiNewRsdp {
+            header: MbTagHeader { tag_type: TagType::AcpiNewRsdp as u32, size: (size_of::<MbTagHeader>() + RSDP_V2_LEN) as u32 },
+            signature: *RSDP_SIGNATURE,
+            checksum: 0,
+            oem_id: *b"BOCHS ",
+            revision: 2,
+            rsdt_address: 0x7fe_1000,
+            length: RSDP_V2_LEN as u32,
+            xsdt_address: 0x7fe_2000,
+            extended_checksum: 0,
+            reserved: [0; 3],
+        };
+
+        let sum = |bytes: &[u8]| bytes.iter().fold(0u8, |acc, &b| acc.wrapping_sub(b));
+        rsdp.checksum = sum(rsdp.bytes(RSDP_V1_LEN));
+        rsdp.extended_checksum = sum(rsdp.bytes(RSDP_V2_LEN));
+        rsdp
+    }
+
+    #[test]
+    fn accepts_valid_rsdps() {
+        let rsdp = valid_rsdp();
+        assert!(rsdp.validate().is_ok());
+        assert_eq!(rsdp.revision(), 2);
+        assert_eq!(rsdp.oem_id(), b"BOCHS ");
+        assert_eq!(rsdp.xsdt_address().unwrap(), 0x7fe_2000);
+    }
+
+    #[test]
+    fn rejects_bad_checksums() {
+        // only covered by the extended checksum
+        let mut rsdp = valid_rsdp();
+        rsdp.xsdt_address += 1;
+        assert!(matches!(rsdp.validate(), Err(AcpiRsdpError::BadChecksum)));
+        assert!(rsdp.xsdt_address().is_err());
+
+        // covered by both, the extended checksum still adds up
+        let mut rsdp = valid_rsdp();
+        rsdp.revision += 1;
+        rsdp.extended_checksum -= 1;
+        assert!(matches!(rsdp.validate(), Err(AcpiRsdpError::BadChecksum)));
+    }
+
+    #[test]
+    fn rejects_bad_lengths() {
+        // shorter than an ACPI 2.0 RSDP, and longer than the tag
+        for (length, tag_size) in [(RSDP_V1_LEN, RSDP_V2_LEN), (RSDP_V2_LEN + 1, RSDP_V2_LEN)] {
+            let mut rsdp = valid_rsdp();
+            rsdp.length = length as u32;
+            rsdp.header.size = (size_of::<MbTagHeader>() + tag_size) as u32;
+            assert!(matches!(rsdp.validate(), Err(AcpiRsdpError::BadLength)));
+        }
+
+        let mut rsdp = valid_rsdp();
+        rsdp.signature[0] = b'X';
+        assert!(matches!(rsdp.validate(), Err(AcpiRsdpError::BadSignature)));
+    }
+}
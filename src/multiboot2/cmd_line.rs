@@ -1,4 +1,4 @@
-use super::{tag_trait::MbTag, MbTagHeader, TagType};
+use super::{tag_trait::{parse_tag_string, MbTag, StringTagError}, MbTagHeader, TagType};
 
 #[repr(C)]
 #[derive(ptr_meta::Pointee)]
@@ -7,17 +7,9 @@ pub(crate) struct CmdLine {
     string: [u8],
 }
 
-#[derive(Debug)]
-pub(crate) enum CmdLineError {
-    StringMissingNull,
-    StringNotUtf8,
-}
-
 impl CmdLine {
-    pub(crate) fn string(&self) -> Result<&str, CmdLineError> {
-        // get the cstr using ffi and return it as a &str
-        let cstr = core::ffi::CStr::from_bytes_until_nul(&self.string).map_err(|_| CmdLineError::StringMissingNull)?;
-        cstr.to_str().map_err(|_| CmdLineError::StringNotUtf8)
+    pub(crate) fn string(&self) -> Result<&str, StringTagError> {
+        parse_tag_string(self.header.size, &self.string, size_of::<MbTagHeader>())
     }
 }
 
@@ -25,7 +17,7 @@ impl MbTag for CmdLine {
     const TAG_TYPE: TagType = TagType::CmdLine;
 
     fn dst_size(base_tag: &MbTagHeader) -> usize {
-        base_tag.size as usize - size_of::<MbTagHeader>()
+        (base_tag.size as usize).saturating_sub(size_of::<MbTagHeader>())
     }
     
 }
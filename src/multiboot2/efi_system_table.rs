@@ -1,4 +1,5 @@
-use super::{tag_trait::MbTag, MbTagHeader, TagType};
+use super::{tag_trait::MbTag, MbBootInfo, MbTagHeader, TagType};
+use crate::memory::PhysicalAddress;
 
 /*
  * I don't think this tag will even exist in this context as the kernel is 64bit.
@@ -16,6 +17,33 @@ pub(crate) struct Efi64BitSystemTablePtr {
     pub(crate) pointer: u64,
 }
 
+#[derive(Debug)]
+pub(crate) enum EfiSystemTableError {
+    TagNotPresent,
+    Only32BitTable,
+}
+
+impl Efi64BitSystemTablePtr {
+    pub(crate) fn address(&self) -> PhysicalAddress {
+        self.pointer as PhysicalAddress
+    }
+}
+
+/*
+ * Returns the address of the 64bit EFI system table.
+ * If the bootloader only gave us the 32bit pointer, this fails instead of using it as this kernel can't use a 32bit system table.
+ */
+pub(crate) fn system_table_address(mb_info: &MbBootInfo) -> Result<PhysicalAddress, EfiSystemTableError> {
+    if let Some(tag) = mb_info.get_tag::<Efi64BitSystemTablePtr>() {
+        return Ok(tag.address());
+    }
+
+    match mb_info.get_tag::<Efi32BitSystemTablePtr>() {
+        Some(_) => Err(EfiSystemTableError::Only32BitTable),
+        None => Err(EfiSystemTableError::TagNotPresent),
+    }
+}
+
 impl MbTag for Efi32BitSystemTablePtr {
     const TAG_TYPE: TagType = TagType::Efi32BitSystemTablePtr;
 
@@ -1,4 +1,5 @@
 use super::{tag_trait::MbTag, MbTagHeader, TagType};
+use crate::memory::{PhysicalAddress, PAGE_SIZE};
 use core::{marker::PhantomData, ptr::{addr_of, slice_from_raw_parts}};
 
 #[repr(C)]
@@ -31,6 +32,12 @@ pub(crate) enum MemoryMapEntryType {
 }
 
 impl MemoryMapEntry {
+    // entries are normally only read from the mb2 info, this allows crafting memory maps in tests
+    #[cfg(test)]
+    pub(crate) fn new(base_addr: u64, length: u64, entry_type: u32) -> Self {
+        MemoryMapEntry { base_addr, length, entry_type, reserved: 0 }
+    }
+
     pub(crate) fn entry_type(&self) -> MemoryMapEntryType {
         match self.entry_type {
             1 => MemoryMapEntryType::AvailableRAM,
@@ -40,22 +47,46 @@ impl MemoryMapEntry {
             other => MemoryMapEntryType::Reserved(other)
         }
     }
+
+    // the length of the area counted in whole frames, with the same boundaries used by the frame allocator
+    pub(crate) fn aligned_length(&self) -> u64 {
+        let frame_size = PAGE_SIZE as u64;
+        let first = self.base_addr / frame_size;
+        let after_last = self.base_addr.saturating_add(self.length) / frame_size;
+
+        (after_last - first) * frame_size
+    }
 }
 
 #[derive(Debug)]
 pub(crate) enum MemoryMapError {
     EntriesInvalidSize,
+    EntrySizeNotAligned,
+    TagTooSmall,
+    TrailingBytes,
 }
 
 impl<'a> MemoryMap<'a> {
     pub(crate) fn entries(&self) -> Result<MemoryMapEntries, MemoryMapError> {
-        // make sure that the data in the tag is consistent
+        // make sure that the data in the tag is consistent (the spec guarantees entry_size to be a multiple of 8)
+        if !self.entry_size.is_multiple_of(8) {
+            return Err(MemoryMapError::EntrySizeNotAligned);
+        }
+
         if self.entry_size as usize != size_of::<MemoryMapEntry>() {
             return Err(MemoryMapError::EntriesInvalidSize);
         }
 
+        // the entries must fill the rest of the tag exactly, otherwise the tag is malformed
+        let entries_size = (self.header.size as usize)
+            .checked_sub(size_of::<MbTagHeader>() + size_of::<u32>() * 2)
+            .ok_or(MemoryMapError::TagTooSmall)?;
+        if !entries_size.is_multiple_of(size_of::<MemoryMapEntry>()) {
+            return Err(MemoryMapError::TrailingBytes);
+        }
+
         // build the slice ref with the correct metadata
-        let entry_count = (self.header.size as usize - size_of::<MbTagHeader>() - size_of::<u32>() * 2) / size_of::<MemoryMapEntry>();
+        let entry_count = entries_size / size_of::<MemoryMapEntry>();
         let ptr = addr_of!(self.entries) as *const MemoryMapEntry;
         let entries = unsafe { &*slice_from_raw_parts(ptr, entry_count) };
 
@@ -67,7 +98,7 @@ impl<'a> MbTag for MemoryMap<'a> {
     const TAG_TYPE: TagType = TagType::MemoryMap;
 
     fn dst_size(base_tag: &MbTagHeader) -> Self::Metadata {
-        base_tag.size as usize - size_of::<MbTagHeader>() - size_of::<u32>() * 2
+        (base_tag.size as usize).saturating_sub(size_of::<MbTagHeader>() + size_of::<u32>() * 2)
     }
 }
 
@@ -76,6 +107,27 @@ impl<'a> MbTag for MemoryMap<'a> {
 #[derive(Clone, Copy)]
 pub(crate) struct MemoryMapEntries<'a>(&'a [MemoryMapEntry]);
 
+impl<'a> MemoryMapEntries<'a> {
+    /*
+     * Yields (start, length) for every run of entries with the same type where each one starts right where the previous one ends.
+     * The entries are merged in the order given by the bootloader, so unsorted maps are not fully coalesced.
+     */
+    pub(crate) fn coalesced(&self) -> impl Iterator<Item = (PhysicalAddress, u64)> + 'a {
+        let mut entries = self.into_iter().peekable();
+
+        core::iter::from_fn(move || {
+            let first = entries.next()?;
+            let mut end = first.base_addr.saturating_add(first.length);
+
+            while let Some(next) = entries.next_if(|next| next.entry_type == first.entry_type && next.base_addr == end) {
+                end = end.saturating_add(next.length);
+            }
+
+            Some((first.base_addr as PhysicalAddress, end - first.base_addr))
+        })
+    }
+}
+
 impl<'a> IntoIterator for MemoryMapEntries<'a> {
     type Item = &'a MemoryMapEntry;
     type IntoIter = MemoryMapEntryIter<'a>;
@@ -107,3 +159,70 @@ impl<'a> Iterator for MemoryMapEntryIter<'a> {
         return Some(&self.entries[self.curr_mem_entry_idx - 1]);
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::multiboot2::{tests::boot_info, MbBootInfo};
+
+    // the memory map tag payload: entry size, entry version and then the entries (as type, base and length)
+    pub(crate) fn payload(entry_size: u32, entries: &[(u32, u64, u64)], extra: usize) -> Vec<u8> {
+        let mut bytes = [entry_size.to_le_bytes(), 0u32.to_le_bytes()].concat();
+        for &(entry_type, base_addr, length) in entries {
+            bytes.extend_from_slice(&base_addr.to_le_bytes());
+            bytes.extend_from_slice(&length.to_le_bytes());
+            bytes.extend_from_slice(&entry_type.to_le_bytes());
+            bytes.extend_from_slice(&[0; 4]);
+        }
+
+        bytes.resize(bytes.len() + extra, 0);
+        bytes
+    }
+
+    fn read_entries(payload: &[u8]) -> Result<Vec<(MemoryMapEntryType, u64, u64)>, MemoryMapError> {
+        let buf = boot_info(&[(TagType::MemoryMap as u32, payload)]);
+        let mb_info = unsafe { MbBootInfo::new(buf.as_ptr().cast()) }.unwrap();
+        let entries = mb_info.get_tag::<MemoryMap>().unwrap().entries()?;
+        Ok(entries.into_iter().map(|entry| (entry.entry_type(), entry.base_addr, entry.length)).collect())
+    }
+
+    #[test]
+    fn reads_entries() {
+        let entries = read_entries(&payload(24, &[(1, 0x0, 0x9fc00), (2, 0xf0000, 0x10000)], 0)).unwrap();
+        assert_eq!(entries, [(MemoryMapEntryType::AvailableRAM, 0x0, 0x9fc00), (MemoryMapEntryType::Reserved(2), 0xf0000, 0x10000)]);
+        assert!(read_entries(&payload(24, &[], 0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_entry_sizes() {
+        assert!(matches!(read_entries(&payload(20, &[(1, 0, 0x1000)], 0)), Err(MemoryMapError::EntrySizeNotAligned)));
+        assert!(matches!(read_entries(&payload(32, &[(1, 0, 0x1000)], 8)), Err(MemoryMapError::EntriesInvalidSize)));
+    }
+
+    #[test]
+    fn rejects_bad_tag_sizes() {
+        assert!(matches!(read_entries(&payload(24, &[(1, 0, 0x1000)], 8)), Err(MemoryMapError::TrailingBytes)));
+
+        // the tag ends in the middle of the entry version
+        assert!(matches!(read_entries(&24u32.to_le_bytes()), Err(MemoryMapError::TagTooSmall)));
+    }
+
+    #[test]
+    fn coalesces_adjacent_entries_of_the_same_type() {
+        let entries = [
+            // three adjacent available entries
+            MemoryMapEntry::new(0x0, 0x1000, 1),
+            MemoryMapEntry::new(0x1000, 0x2000, 1),
+            MemoryMapEntry::new(0x3000, 0x1000, 1),
+            // touching the previous ones, but reserved
+            MemoryMapEntry::new(0x4000, 0x1000, 2),
+            // available again, with a gap in between
+            MemoryMapEntry::new(0x10000, 0x1000, 1),
+            MemoryMapEntry::new(0x12000, 0x1000, 1),
+        ];
+
+        let ranges: Vec<_> = MemoryMapEntries(&entries).coalesced().collect();
+        assert_eq!(ranges, [(0x0, 0x4000), (0x4000, 0x1000), (0x10000, 0x1000), (0x12000, 0x1000)]);
+        assert_eq!(MemoryMapEntries(&[]).coalesced().count(), 0);
+    }
+}
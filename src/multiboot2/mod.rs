@@ -15,7 +15,9 @@ pub mod efi_system_table;
 pub mod efi_boot_services_not_terminated;
 pub mod efi_image_handle;
 pub mod image_load_base_phy_addr;
+pub mod acpi_new_rsdp;
 
+use memory_map::{MemoryMap, MemoryMapEntryType};
 use tag_iter::MbTagIter;
 use tag_trait::MbTag;
 
@@ -27,14 +29,18 @@ struct MbBootInformationHeader {
     // followed by the tags
 }
 
+/*
+ * The tag type is kept as a raw u32 as the bootloader may give us types that we don't know about
+ * (and reading those as a `TagType` would be undefined behaviour).
+ */
 #[repr(C)]
 pub(crate) struct MbTagHeader {
-    tag_type: TagType,
+    tag_type: u32,
     size: u32,
 }
 
 #[repr(u32)]
-#[derive(PartialEq)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub(crate) enum TagType {
     End = 0,
     CmdLine = 1,
@@ -60,6 +66,39 @@ pub(crate) enum TagType {
     ImageLoadBasePhysicalAdress = 21,
 }
 
+impl TryFrom<u32> for TagType {
+    type Error = u32;
+
+    // on failure, the unknown type is given back
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => TagType::End,
+            1 => TagType::CmdLine,
+            2 => TagType::BootLoaderName,
+            3 => TagType::Modules,
+            4 => TagType::BasicMemoryInfo,
+            5 => TagType::BiosBootDevice,
+            6 => TagType::MemoryMap,
+            7 => TagType::VbeInfo,
+            8 => TagType::FrameBufferInfo,
+            9 => TagType::ElfSymbols,
+            10 => TagType::ApmTable,
+            11 => TagType::Efi32BitSystemTablePtr,
+            12 => TagType::Efi64BitSystemTablePtr,
+            13 => TagType::SmBiosTables,
+            14 => TagType::AcpiOldRsdp,
+            15 => TagType::AcpiNewRsdp,
+            16 => TagType::NetworkingInfo,
+            17 => TagType::EfiMemoryMap,
+            18 => TagType::EfiBootServicesNotTerminated,
+            19 => TagType::Efi32BitImageHandlePtr,
+            20 => TagType::Efi64BitImageHandlePtr,
+            21 => TagType::ImageLoadBasePhysicalAdress,
+            other => return Err(other),
+        })
+    }
+}
+
 impl MbTagHeader {
     fn cast_to<T: MbTag + ?Sized>(&self) -> &T {
         // Safety: At this point, we take the data as being valid as it was already checked.
@@ -81,6 +120,8 @@ pub(crate) struct MbBootInfo {
 pub(crate) enum MbBootInfoError {
     Not64BitAligned,
     NullPtr,
+    TagOverrun,
+    MissingEndTag,
 }
 
 impl MbBootInfo {
@@ -108,13 +149,171 @@ impl MbBootInfo {
         self.header.total_size
     }
 
+    /*
+     * Walks the whole tag list without trusting it, making sure that every tag is at least as big as its header,
+     * that no tag goes past `total_size` and that the list is terminated by an `End` tag.
+     * This should be called before getting any tags as the other methods assume a well formed list.
+     */
+    pub fn validate(&self) -> Result<(), MbBootInfoError> {
+        let header_size = size_of::<MbTagHeader>();
+        let tags_size = (self.header.total_size as usize)
+            .checked_sub(size_of::<MbBootInformationHeader>())
+            .ok_or(MbBootInfoError::TagOverrun)?;
+
+        let mut offset = 0;
+        while offset < tags_size {
+            if tags_size - offset < header_size {
+                return Err(MbBootInfoError::TagOverrun);
+            }
+
+            // Safety: the whole tag header was just checked to be within `total_size`
+            let tag = unsafe { &*self.tags_ptr.byte_add(offset) };
+            let tag_size = tag.size as usize;
+            if tag_size < header_size || tag_size > tags_size - offset {
+                return Err(MbBootInfoError::TagOverrun);
+            }
+
+            if tag.tag_type == TagType::End as u32 {
+                return Ok(());
+            }
+
+            // tags are 8 byte aligned
+            offset += (tag_size + 7) & !7;
+        }
+
+        Err(MbBootInfoError::MissingEndTag)
+    }
+
+    // usable RAM in bytes, counting only the whole frames that the frame allocator can hand out (0 without a valid memory map)
+    pub fn total_usable_memory(&self) -> u64 {
+        self.get_tag::<MemoryMap>()
+            .and_then(|mem_map| mem_map.entries().ok())
+            .map_or(0, |entries| {
+                entries.into_iter()
+                    .filter(|entry| entry.entry_type() == MemoryMapEntryType::AvailableRAM)
+                    .map(|entry| entry.aligned_length())
+                    .sum()
+            })
+    }
+
+    // size in bytes of every area in the memory map, whatever its type (0 without a valid memory map)
+    pub fn total_memory(&self) -> u64 {
+        self.get_tag::<MemoryMap>()
+            .and_then(|mem_map| mem_map.entries().ok())
+            .map_or(0, |entries| entries.into_iter().map(|entry| entry.length).sum())
+    }
+
     fn tags (&self) -> MbTagIter {
         MbTagIter::new(self.tags_ptr)
     }
 
+    // the type (or the raw value, if unknown) and size of every tag, in the order given by the bootloader
+    pub fn tag_types(&self) -> impl Iterator<Item = (Result<TagType, u32>, u32)> + '_ {
+        self.tags().map(|tag| (TagType::try_from(tag.tag_type), tag.size))
+    }
+
     pub fn get_tag<T: MbTag + ?Sized>(&self) -> Option<&T> {
+        self.get_tags::<T>().next()
+    }
+
+    // some tags (like `Modules`, one per module) may show up more than once
+    pub fn get_tags<'a, T: MbTag + ?Sized + 'a>(&'a self) -> impl Iterator<Item = &'a T> + 'a {
         self.tags()
-            .find(|tag| tag.tag_type == T::TAG_TYPE)
+            .filter(|tag| tag.tag_type == T::TAG_TYPE as u32)
             .map(|tag| tag.cast_to::<T>())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // builds the boot info out of (type, payload) tags, each one padded to 8 bytes and followed by the end tag
+    pub(crate) fn boot_info(tags: &[(u32, &[u8])]) -> Vec<u64> {
+        let mut bytes = vec![0u8; size_of::<MbBootInformationHeader>()];
+        for &(tag_type, payload) in tags.iter().chain(&[(TagType::End as u32, &[][..])]) {
+            let size = (size_of::<MbTagHeader>() + payload.len()) as u32;
+            bytes.extend_from_slice(&tag_type.to_le_bytes());
+            bytes.extend_from_slice(&size.to_le_bytes());
+            bytes.extend_from_slice(payload);
+            bytes.resize(bytes.len().next_multiple_of(8), 0);
+        }
+
+        let total_size = bytes.len() as u32;
+        bytes[..4].copy_from_slice(&total_size.to_le_bytes());
+        bytes.chunks(8).map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap())).collect()
+    }
+
+    // overwrites the u32 at `offset` bytes into `buf` (like the total size or the size of a tag)
+    fn set_u32(buf: &mut [u64], offset: usize, value: u32) {
+        unsafe { buf.as_mut_ptr().cast::<u8>().add(offset).cast::<u32>().write_unaligned(value) };
+    }
+
+    fn validate(buf: &[u64]) -> Result<(), MbBootInfoError> {
+        unsafe { MbBootInfo::new(buf.as_ptr().cast()) }.unwrap().validate()
+    }
+
+    #[test]
+    fn validates_tag_lists() {
+        // 8 bytes of boot info header, a 16 byte tag, a 13 byte tag (padded to 16) and the end tag
+        let tags: [(u32, &[u8]); 2] = [(TagType::BasicMemoryInfo as u32, &[0; 8]), (0x1234, &[0xff; 5])];
+        let buf = boot_info(&tags);
+        assert!(validate(&buf).is_ok());
+
+        // the tag list is cut in the middle of the second tag, of its header, and before the first tag
+        for total_size in [36, 28, 4] {
+            let mut buf = buf.clone();
+            set_u32(&mut buf, 0, total_size);
+            assert!(matches!(validate(&buf), Err(MbBootInfoError::TagOverrun)), "total size {total_size}");
+        }
+
+        // tags that are smaller than their header or that go past the end of the list
+        for tag_size in [4, 41] {
+            let mut buf = buf.clone();
+            set_u32(&mut buf, 24 + 4, tag_size);
+            assert!(matches!(validate(&buf), Err(MbBootInfoError::TagOverrun)), "tag size {tag_size}");
+        }
+    }
+
+    #[test]
+    fn requires_an_end_tag() {
+        // the list stops right before the end tag
+        let mut buf = boot_info(&[(TagType::BasicMemoryInfo as u32, &[0; 8])]);
+        set_u32(&mut buf, 0, 24);
+        assert!(matches!(validate(&buf), Err(MbBootInfoError::MissingEndTag)));
+
+        // and here there are no tags at all
+        set_u32(&mut buf, 0, 8);
+        assert!(matches!(validate(&buf), Err(MbBootInfoError::MissingEndTag)));
+    }
+
+    #[test]
+    fn lists_every_tag() {
+        let buf = boot_info(&[
+            (TagType::BootLoaderName as u32, b"GRUB 2.12\0"),
+            (0x1234, &[0xff; 5]),
+            (TagType::BasicMemoryInfo as u32, &[0; 8]),
+        ]);
+        let mb_info = unsafe { MbBootInfo::new(buf.as_ptr().cast()) }.unwrap();
+
+        let tags: Vec<_> = mb_info.tag_types().collect();
+        assert_eq!(tags, [(Ok(TagType::BootLoaderName), 18), (Err(0x1234), 13), (Ok(TagType::BasicMemoryInfo), 16)]);
+        assert_eq!(mb_info.size(), 8 + 24 + 16 + 16 + 8);
+    }
+
+    #[test]
+    fn reads_string_tags() {
+        let buf = boot_info(&[(TagType::CmdLine as u32, b"root=/dev/sda1\0"), (TagType::BootLoaderName as u32, b"GRUB\0")]);
+        let mb_info = unsafe { MbBootInfo::new(buf.as_ptr().cast()) }.unwrap();
+
+        assert_eq!(mb_info.get_tag::<cmd_line::CmdLine>().unwrap().string().unwrap(), "root=/dev/sda1");
+        assert_eq!(mb_info.get_tag::<boot_loader_name::BootLoaderName>().unwrap().string().unwrap(), "GRUB");
+    }
+
+    #[test]
+    fn converts_known_tag_types() {
+        assert_eq!(TagType::try_from(0), Ok(TagType::End));
+        assert_eq!(TagType::try_from(21), Ok(TagType::ImageLoadBasePhysicalAdress));
+        assert_eq!(TagType::try_from(22), Err(22));
+    }
+}
@@ -1,4 +1,5 @@
-use super::{tag_trait::MbTag, MbTagHeader, TagType};
+use super::{tag_trait::{parse_tag_string, MbTag, StringTagError}, MbTagHeader, TagType};
+use core::ops::Range;
 
 #[repr(C)]
 #[derive(ptr_meta::Pointee)]
@@ -10,16 +11,37 @@ pub(crate) struct Modules {
 }
 
 #[derive(Debug)]
-pub(crate) enum ModulesError {
-    StringMissingNull,
-    StringNotUtf8,
+pub(crate) enum ModuleError {
+    InvalidRange,
 }
 
 impl Modules {
-    pub(crate) fn string(&self) -> Result<&str, ModulesError> {
-        // get the cstr using ffi and return it as a &str
-        let cstr = core::ffi::CStr::from_bytes_until_nul(&self.string).map_err(|_| ModulesError::StringMissingNull)?;
-        cstr.to_str().map_err(|_| ModulesError::StringNotUtf8)
+    /*
+     * The physical address range of the module (`mod_end` is the address right after the last byte).
+     * A null start or an end before the start can only come from a broken tag, so they are rejected.
+     */
+    pub(crate) fn range(&self) -> Result<Range<usize>, ModuleError> {
+        if self.mod_start == 0 || self.mod_end < self.mod_start {
+            return Err(ModuleError::InvalidRange);
+        }
+
+        Ok(self.mod_start as usize..self.mod_end as usize)
+    }
+
+    /*
+     * The module contents.
+     * The bootloader loads the modules outside of the mb2 info, so they don't live as long as it. They are valid until
+     * something else reuses that memory (the frame allocator does not know about modules).
+     *
+     * Safety: The module memory must still be untouched and identity mapped.
+     */
+    pub(crate) unsafe fn data(&self) -> Result<&[u8], ModuleError> {
+        let range = self.range()?;
+        Ok(core::slice::from_raw_parts(range.start as *const u8, range.len()))
+    }
+
+    pub(crate) fn string(&self) -> Result<&str, StringTagError> {
+        parse_tag_string(self.header.size, &self.string, size_of::<MbTagHeader>() + size_of::<u32>() * 2)
     }
 }
 
@@ -27,6 +49,43 @@ impl MbTag for Modules {
     const TAG_TYPE: TagType = TagType::Modules;
 
     fn dst_size(base_tag: &MbTagHeader) -> Self::Metadata {
-        base_tag.size as usize - size_of::<MbTagHeader>() - size_of::<u32>() * 2
+        (base_tag.size as usize).saturating_sub(size_of::<MbTagHeader>() + size_of::<u32>() * 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // builds a modules tag with the string "disk" (tags are 8 byte aligned, hence the u64s)
+    fn tag(mod_start: u32, mod_end: u32) -> [u64; 3] {
+        let mut tag = [0u64; 3];
+        let size = (size_of::<MbTagHeader>() + size_of::<u32>() * 2 + 5) as u64;
+        tag[0] = TagType::Modules as u64 | size << 32;
+        tag[1] = mod_start as u64 | (mod_end as u64) << 32;
+        tag[2] = u64::from_le_bytes(*b"disk\0\0\0\0");
+        tag
+    }
+
+    fn modules(tag: &[u64; 3]) -> &Modules {
+        unsafe { Modules::from_base_tag(&*tag.as_ptr().cast::<MbTagHeader>()) }
+    }
+
+    #[test]
+    fn checks_the_module_range() {
+        let valid = tag(0x1000, 0x3000);
+        assert_eq!(modules(&valid).range().unwrap(), 0x1000..0x3000);
+        assert_eq!(modules(&valid).string().unwrap(), "disk");
+
+        let empty = tag(0x1000, 0x1000);
+        assert!(modules(&empty).range().unwrap().is_empty());
+
+        // these must be rejected before any pointer is made out of them
+        let null = tag(0, 0);
+        assert!(matches!(modules(&null).range(), Err(ModuleError::InvalidRange)));
+        assert!(matches!(unsafe { modules(&null).data() }, Err(ModuleError::InvalidRange)));
+
+        let backwards = tag(0x3000, 0x1000);
+        assert!(matches!(unsafe { modules(&backwards).data() }, Err(ModuleError::InvalidRange)));
     }
 }
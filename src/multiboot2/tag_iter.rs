@@ -23,14 +23,13 @@ impl<'a> Iterator for MbTagIter<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let curr_tag = unsafe { &*self.curr_tag_addr };
-        match curr_tag.tag_type {
-            TagType::End => None,
-            _ => {
-                // return the current tag and update the ptr to the next one
-                let ptr_offset = ((curr_tag.size as usize + 7) & !7) as isize;
-                self.curr_tag_addr = unsafe { self.curr_tag_addr.byte_offset(ptr_offset) };
-                return Some(curr_tag);
-            }
+        if curr_tag.tag_type == TagType::End as u32 {
+            return None;
         }
+
+        // return the current tag and update the ptr to the next one
+        let ptr_offset = ((curr_tag.size as usize + 7) & !7) as isize;
+        self.curr_tag_addr = unsafe { self.curr_tag_addr.byte_offset(ptr_offset) };
+        Some(curr_tag)
     }
 }
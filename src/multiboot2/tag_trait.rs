@@ -1,4 +1,5 @@
 use super::{MbTagHeader, TagType};
+use core::ffi::CStr;
 use ptr_meta::Pointee;
 
 pub(crate) trait MbTag: Pointee {
@@ -13,3 +14,70 @@ pub(crate) trait MbTag: Pointee {
         &*ptr
     }
 }
+
+#[derive(Debug)]
+pub(crate) enum StringTagError {
+    TagTooSmall,
+    StringMissingNull,
+    StringNotUtf8,
+}
+
+/*
+ * Shared parser for every tag that ends with a null terminated string.
+ * `tag_size` is the size reported in the tag header and `header_overhead` is the amount of bytes in the tag
+ * that come before the string (the header itself plus any fixed fields).
+ * The string is never read past the real length computed from the tag size, even if `bytes` is bigger,
+ * so this can't panic or read out of bounds when handed malformed boot info.
+ */
+pub(crate) fn parse_tag_string(tag_size: u32, bytes: &[u8], header_overhead: usize) -> Result<&str, StringTagError> {
+    let real_len = (tag_size as usize).checked_sub(header_overhead).ok_or(StringTagError::TagTooSmall)?;
+    let bytes = &bytes[..real_len.min(bytes.len())];
+
+    // get the cstr using ffi and return it as a &str
+    let cstr = CStr::from_bytes_until_nul(bytes).map_err(|_| StringTagError::StringMissingNull)?;
+    cstr.to_str().map_err(|_| StringTagError::StringNotUtf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the smallest string tag: just the header, followed by the string
+    const OVERHEAD: usize = size_of::<MbTagHeader>();
+
+    fn parse(bytes: &[u8]) -> Result<&str, StringTagError> {
+        parse_tag_string((OVERHEAD + bytes.len()) as u32, bytes, OVERHEAD)
+    }
+
+    #[test]
+    fn parses_strings() {
+        assert_eq!(parse(b"GRUB 2.12\0").unwrap(), "GRUB 2.12");
+        assert_eq!(parse(b"\0").unwrap(), "");
+
+        // anything after the terminator (like padding) is ignored
+        assert_eq!(parse(b"root=/dev/sda\0\0\0").unwrap(), "root=/dev/sda");
+    }
+
+    #[test]
+    fn rejects_missing_terminator() {
+        assert!(matches!(parse(b"GRUB"), Err(StringTagError::StringMissingNull)));
+        assert!(matches!(parse(b""), Err(StringTagError::StringMissingNull)));
+
+        // a terminator past the size given by the tag does not count
+        let bytes = b"GRUB\0";
+        let size = (OVERHEAD + 4) as u32;
+        assert!(matches!(parse_tag_string(size, bytes, OVERHEAD), Err(StringTagError::StringMissingNull)));
+    }
+
+    #[test]
+    fn rejects_invalid_utf8() {
+        assert!(matches!(parse(b"GR\xffUB\0"), Err(StringTagError::StringNotUtf8)));
+        assert!(matches!(parse(b"\xc3\0"), Err(StringTagError::StringNotUtf8)));
+    }
+
+    #[test]
+    fn rejects_tags_smaller_than_their_header() {
+        let size = (OVERHEAD - 1) as u32;
+        assert!(matches!(parse_tag_string(size, b"GRUB\0", OVERHEAD), Err(StringTagError::TagTooSmall)));
+    }
+}
@@ -0,0 +1,23 @@
+pub mod partition;
+pub mod ramdisk;
+
+#[derive(Debug)]
+pub enum BlockError {
+    OutOfRange,
+    InvalidBufferSize,
+}
+
+/*
+ * A source of fixed size blocks, addressed by their LBA (logical block address).
+ * `buf` must be exactly `block_size()` bytes long.
+ */
+pub trait BlockRead {
+    fn block_size(&self) -> usize;
+    fn block_count(&self) -> u64;
+    fn read_block(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError>;
+}
+
+// same rules as `BlockRead`, `buf` must be exactly `block_size()` bytes long
+pub trait BlockWrite: BlockRead {
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError>;
+}
@@ -0,0 +1,444 @@
+// https://wiki.osdev.org/MBR_(x86)
+// https://wiki.osdev.org/GPT
+use super::{BlockError, BlockRead};
+use crate::hash::crc32::{self, Crc32};
+
+// the biggest logical block size that we support (4Kn disks)
+const MAX_BLOCK_SIZE: usize = 4096;
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+const MBR_SIGNATURE_OFFSET: usize = 510;
+const MBR_ENTRIES_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_ENTRY_COUNT: usize = 4;
+const MBR_TYPE_EMPTY: u8 = 0x00;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_HEADER_LBA: u64 = 1;
+const GPT_MIN_HEADER_SIZE: usize = 92;
+const GPT_HEADER_CRC_OFFSET: usize = 16;
+const GPT_MIN_ENTRY_SIZE: usize = 128;
+
+#[derive(Debug)]
+pub enum PartitionError {
+    BlockErr(BlockError),
+    UnsupportedBlockSize,
+    ScratchTooSmall,
+    MissingMbrSignature,
+    InvalidGptSignature,
+    InvalidGptHeaderSize,
+    GptHeaderBadChecksum,
+    InvalidGptEntrySize,
+    GptEntriesBadChecksum,
+    InvalidGptUsableRange,
+    InvalidGptPartitionRange,
+}
+
+impl From<BlockError> for PartitionError {
+    fn from(err: BlockError) -> Self {
+        PartitionError::BlockErr(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Guid(pub [u8; 16]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+    Mbr(u8), // the partition type byte
+    Gpt(Guid), // the partition type GUID
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Partition {
+    pub start_lba: u64,
+    pub block_count: u64,
+    pub kind: PartitionKind,
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/*
+ * Parses the partition table of `device`.
+ * A protective MBR (an entry with type 0xEE) means that the disk uses GPT, in which case the GPT header and
+ * the partition entry array are validated with their CRC-32 before any entry is handed out.
+ *
+ * `scratch` holds one block at a time (the returned iterator keeps using it to read the GPT entries), so it must be
+ * at least as big as a device block. Keeping it outside of the stack matters as the kernel stack is small and has no guard page.
+ */
+pub fn parse<'a, D: BlockRead>(device: &'a D, scratch: &'a mut [u8]) -> Result<PartitionIter<'a, D>, PartitionError> {
+    let block_size = device.block_size();
+    if !(512..=MAX_BLOCK_SIZE).contains(&block_size) {
+        return Err(PartitionError::UnsupportedBlockSize);
+    }
+
+    let block = scratch.get_mut(..block_size).ok_or(PartitionError::ScratchTooSmall)?;
+    device.read_block(0, block)?;
+
+    if block[MBR_SIGNATURE_OFFSET..MBR_SIGNATURE_OFFSET + 2] != MBR_SIGNATURE {
+        return Err(PartitionError::MissingMbrSignature);
+    }
+
+    let mut mbr_entries = [[0u8; MBR_ENTRY_SIZE]; MBR_ENTRY_COUNT];
+    let mut is_protective = false;
+    for (i, entry) in mbr_entries.iter_mut().enumerate() {
+        let offset = MBR_ENTRIES_OFFSET + i * MBR_ENTRY_SIZE;
+        entry.copy_from_slice(&block[offset..offset + MBR_ENTRY_SIZE]);
+        is_protective |= entry[4] == MBR_TYPE_GPT_PROTECTIVE;
+    }
+
+    let table = match is_protective {
+        true => Table::Gpt(parse_gpt_header(device, block)?),
+        false => Table::Mbr(mbr_entries),
+    };
+
+    Ok(PartitionIter {
+        device,
+        block,
+        block_lba: None,
+        table,
+        curr_entry_idx: 0,
+    })
+}
+
+fn parse_gpt_header<D: BlockRead>(device: &D, block: &mut [u8]) -> Result<GptInfo, PartitionError> {
+    device.read_block(GPT_HEADER_LBA, block)?;
+
+    if &block[0..8] != GPT_SIGNATURE {
+        return Err(PartitionError::InvalidGptSignature);
+    }
+
+    let header_size = read_u32(block, 12) as usize;
+    if header_size < GPT_MIN_HEADER_SIZE || header_size > block.len() {
+        return Err(PartitionError::InvalidGptHeaderSize);
+    }
+
+    // the header checksum is calculated with the checksum field itself set to 0
+    let header_crc = read_u32(block, GPT_HEADER_CRC_OFFSET);
+    block[GPT_HEADER_CRC_OFFSET..GPT_HEADER_CRC_OFFSET + 4].fill(0);
+    if crc32::checksum(&block[..header_size]) != header_crc {
+        return Err(PartitionError::GptHeaderBadChecksum);
+    }
+
+    let gpt = GptInfo {
+        first_usable_lba: read_u64(block, 40),
+        last_usable_lba: read_u64(block, 48),
+        entries_lba: read_u64(block, 72),
+        entry_count: read_u32(block, 80) as usize,
+        entry_size: read_u32(block, 84) as usize,
+    };
+    let entries_crc = read_u32(block, 88);
+
+    // the usable lbas can never include the protective MBR or the header itself
+    if gpt.first_usable_lba <= GPT_HEADER_LBA || gpt.last_usable_lba < gpt.first_usable_lba {
+        return Err(PartitionError::InvalidGptUsableRange);
+    }
+
+    // entries must be at least 128 bytes, a power of 2 and never cross a block boundary
+    if gpt.entry_size < GPT_MIN_ENTRY_SIZE || !gpt.entry_size.is_power_of_two() || gpt.entry_size > block.len() {
+        return Err(PartitionError::InvalidGptEntrySize);
+    }
+
+    // the checksum covers the whole entry array, which may span many blocks
+    let mut crc = Crc32::new();
+    let mut remaining = gpt.entry_count * gpt.entry_size;
+    let mut lba = gpt.entries_lba;
+    while remaining > 0 {
+        device.read_block(lba, block)?;
+        let len = remaining.min(block.len());
+        crc.update(&block[..len]);
+
+        remaining -= len;
+        lba += 1;
+    }
+
+    if crc.finalize() != entries_crc {
+        return Err(PartitionError::GptEntriesBadChecksum);
+    }
+
+    Ok(gpt)
+}
+
+#[derive(Clone, Copy)]
+struct GptInfo {
+    first_usable_lba: u64,
+    last_usable_lba: u64, // inclusive
+    entries_lba: u64,
+    entry_count: usize,
+    entry_size: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Table {
+    Mbr([[u8; MBR_ENTRY_SIZE]; MBR_ENTRY_COUNT]),
+    Gpt(GptInfo),
+}
+
+/*
+ * Yields every used partition entry. Unused slots (MBR type 0 or a zeroed GPT type GUID) are skipped.
+ * GPT entries are read lazily from the device, so reading them may still fail.
+ */
+pub struct PartitionIter<'a, D: BlockRead> {
+    device: &'a D,
+    block: &'a mut [u8], // exactly one device block long
+    block_lba: Option<u64>, // the lba currently held in `block`
+    table: Table,
+    curr_entry_idx: usize,
+}
+
+impl<'a, D: BlockRead> PartitionIter<'a, D> {
+    fn next_mbr(&mut self, entries: &[[u8; MBR_ENTRY_SIZE]; MBR_ENTRY_COUNT]) -> Option<Partition> {
+        while self.curr_entry_idx < MBR_ENTRY_COUNT {
+            let entry = &entries[self.curr_entry_idx];
+            self.curr_entry_idx += 1;
+
+            if entry[4] == MBR_TYPE_EMPTY {
+                continue;
+            }
+
+            return Some(Partition {
+                start_lba: read_u32(entry, 8) as u64,
+                block_count: read_u32(entry, 12) as u64,
+                kind: PartitionKind::Mbr(entry[4]),
+            });
+        }
+
+        None
+    }
+
+    fn next_gpt(&mut self, gpt: GptInfo) -> Option<Result<Partition, PartitionError>> {
+        let entries_per_block = self.block.len() / gpt.entry_size;
+
+        while self.curr_entry_idx < gpt.entry_count {
+            let lba = gpt.entries_lba + (self.curr_entry_idx / entries_per_block) as u64;
+            let offset = (self.curr_entry_idx % entries_per_block) * gpt.entry_size;
+            self.curr_entry_idx += 1;
+
+            // consecutive entries share a block, so it is only read again once the lba changes
+            if self.block_lba != Some(lba) {
+                // a failed read may leave the buffer half written
+                self.block_lba = None;
+                if let Err(err) = self.device.read_block(lba, self.block) {
+                    return Some(Err(err.into()));
+                }
+
+                self.block_lba = Some(lba);
+            }
+
+            let entry = &self.block[offset..offset + gpt.entry_size];
+            let type_guid = Guid(entry[0..16].try_into().unwrap());
+            if type_guid.0 == [0; 16] {
+                continue;
+            }
+
+            // the last lba is inclusive and the partition must be within the usable lbas given by the header
+            let first_lba = read_u64(entry, 32);
+            let last_lba = read_u64(entry, 40);
+            if last_lba < first_lba || first_lba < gpt.first_usable_lba || last_lba > gpt.last_usable_lba {
+                return Some(Err(PartitionError::InvalidGptPartitionRange));
+            }
+
+            return Some(Ok(Partition {
+                start_lba: first_lba,
+                block_count: last_lba - first_lba + 1,
+                kind: PartitionKind::Gpt(type_guid),
+            }));
+        }
+
+        None
+    }
+}
+
+impl<'a, D: BlockRead> Iterator for PartitionIter<'a, D> {
+    type Item = Result<Partition, PartitionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.table {
+            Table::Mbr(entries) => self.next_mbr(&entries).map(Ok),
+            Table::Gpt(gpt) => self.next_gpt(gpt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /*
+     * The first 34 blocks (protective MBR, header and the 128 entry array) of a 2048 block disk with an
+     * EFI system partition (34..=1057) and a linux partition (1058..=2014). The CRCs were made with zlib.
+     */
+    const GPT_DISK: &[u8] = include_bytes!("testdata/gpt.bin");
+    const BLOCK_SIZE: usize = 512;
+
+    const ESP_GUID: [u8; 16] = [0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b];
+    const LINUX_GUID: [u8; 16] = [0xaf, 0x3d, 0xc6, 0x0f, 0x83, 0x84, 0x72, 0x47, 0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d, 0xe4];
+
+    // a disk image in memory that counts how many blocks were read from it
+    struct Disk<'a> {
+        data: &'a [u8],
+        reads: Cell<usize>,
+    }
+
+    impl<'a> Disk<'a> {
+        fn new(data: &'a [u8]) -> Self {
+            Disk { data, reads: Cell::new(0) }
+        }
+    }
+
+    impl<'a> BlockRead for Disk<'a> {
+        fn block_size(&self) -> usize {
+            BLOCK_SIZE
+        }
+
+        fn block_count(&self) -> u64 {
+            (self.data.len() / BLOCK_SIZE) as u64
+        }
+
+        fn read_block(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+            let start = lba as usize * BLOCK_SIZE;
+            let block = self.data.get(start..start + BLOCK_SIZE).ok_or(BlockError::OutOfRange)?;
+            buf.copy_from_slice(block);
+            self.reads.set(self.reads.get() + 1);
+            Ok(())
+        }
+    }
+
+    fn header_mut(disk: &mut [u8]) -> &mut [u8] {
+        &mut disk[BLOCK_SIZE..BLOCK_SIZE + GPT_MIN_HEADER_SIZE]
+    }
+
+    fn entry_mut(disk: &mut [u8], idx: usize) -> &mut [u8] {
+        let offset = 2 * BLOCK_SIZE + idx * GPT_MIN_ENTRY_SIZE;
+        &mut disk[offset..offset + GPT_MIN_ENTRY_SIZE]
+    }
+
+    // makes both checksums valid again after the header or the entries were changed
+    fn fix_checksums(disk: &mut [u8]) {
+        let entries_crc = crc32::checksum(&disk[2 * BLOCK_SIZE..34 * BLOCK_SIZE]);
+        let header = header_mut(disk);
+        header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+        header[GPT_HEADER_CRC_OFFSET..GPT_HEADER_CRC_OFFSET + 4].fill(0);
+
+        let header_crc = crc32::checksum(header);
+        header[GPT_HEADER_CRC_OFFSET..GPT_HEADER_CRC_OFFSET + 4].copy_from_slice(&header_crc.to_le_bytes());
+    }
+
+    fn partitions(disk: &[u8]) -> Result<Vec<Result<Partition, PartitionError>>, PartitionError> {
+        let device = Disk::new(disk);
+        let mut scratch = [0u8; BLOCK_SIZE];
+        Ok(parse(&device, &mut scratch)?.collect())
+    }
+
+    #[test]
+    fn parses_gpt() {
+        let parts = partitions(GPT_DISK).unwrap();
+        assert_eq!(parts.len(), 2);
+
+        let esp = parts[0].as_ref().unwrap();
+        assert_eq!((esp.start_lba, esp.block_count), (34, 1024));
+        assert_eq!(esp.kind, PartitionKind::Gpt(Guid(ESP_GUID)));
+
+        let linux = parts[1].as_ref().unwrap();
+        assert_eq!((linux.start_lba, linux.block_count), (1058, 957));
+        assert_eq!(linux.kind, PartitionKind::Gpt(Guid(LINUX_GUID)));
+    }
+
+    #[test]
+    fn parses_mbr() {
+        let mut disk = vec![0u8; 4 * BLOCK_SIZE];
+        disk[MBR_SIGNATURE_OFFSET..MBR_SIGNATURE_OFFSET + 2].copy_from_slice(&MBR_SIGNATURE);
+
+        // the second slot is used while the first one is left empty
+        let entry = &mut disk[MBR_ENTRIES_OFFSET + MBR_ENTRY_SIZE..MBR_ENTRIES_OFFSET + 2 * MBR_ENTRY_SIZE];
+        entry[4] = 0x0c;
+        entry[8..12].copy_from_slice(&2048u32.to_le_bytes());
+        entry[12..16].copy_from_slice(&4096u32.to_le_bytes());
+
+        let parts = partitions(&disk).unwrap();
+        assert_eq!(parts.len(), 1);
+
+        let part = parts[0].as_ref().unwrap();
+        assert_eq!((part.start_lba, part.block_count), (2048, 4096));
+        assert_eq!(part.kind, PartitionKind::Mbr(0x0c));
+    }
+
+    #[test]
+    fn rejects_bad_header_checksum() {
+        let mut disk = GPT_DISK.to_vec();
+        header_mut(&mut disk)[GPT_HEADER_CRC_OFFSET] ^= 1;
+        assert!(matches!(partitions(&disk), Err(PartitionError::GptHeaderBadChecksum)));
+    }
+
+    #[test]
+    fn rejects_bad_entries_checksum() {
+        let mut disk = GPT_DISK.to_vec();
+        entry_mut(&mut disk, 0)[32] ^= 1;
+        assert!(matches!(partitions(&disk), Err(PartitionError::GptEntriesBadChecksum)));
+    }
+
+    #[test]
+    fn rejects_entry_spanning_the_whole_lba_space() {
+        let mut disk = GPT_DISK.to_vec();
+        let entry = entry_mut(&mut disk, 1);
+        entry[32..40].copy_from_slice(&0u64.to_le_bytes());
+        entry[40..48].copy_from_slice(&u64::MAX.to_le_bytes());
+        fix_checksums(&mut disk);
+
+        let parts = partitions(&disk).unwrap();
+        assert!(parts[0].is_ok());
+        assert!(matches!(parts[1], Err(PartitionError::InvalidGptPartitionRange)));
+    }
+
+    #[test]
+    fn rejects_entries_outside_the_usable_range() {
+        // first lba after the last lba
+        let mut disk = GPT_DISK.to_vec();
+        let entry = entry_mut(&mut disk, 0);
+        entry[32..40].copy_from_slice(&100u64.to_le_bytes());
+        entry[40..48].copy_from_slice(&99u64.to_le_bytes());
+        fix_checksums(&mut disk);
+        assert!(matches!(partitions(&disk).unwrap()[0], Err(PartitionError::InvalidGptPartitionRange)));
+
+        // ends past the last usable lba (2014)
+        let mut disk = GPT_DISK.to_vec();
+        entry_mut(&mut disk, 1)[40..48].copy_from_slice(&2015u64.to_le_bytes());
+        fix_checksums(&mut disk);
+        assert!(matches!(partitions(&disk).unwrap()[1], Err(PartitionError::InvalidGptPartitionRange)));
+    }
+
+    #[test]
+    fn rejects_usable_range_covering_the_header() {
+        let mut disk = GPT_DISK.to_vec();
+        header_mut(&mut disk)[40..48].copy_from_slice(&0u64.to_le_bytes());
+        fix_checksums(&mut disk);
+        assert!(matches!(partitions(&disk), Err(PartitionError::InvalidGptUsableRange)));
+    }
+
+    #[test]
+    fn reads_each_entry_block_once() {
+        let device = Disk::new(GPT_DISK);
+        let mut scratch = [0u8; BLOCK_SIZE];
+        let iter = parse(&device, &mut scratch).unwrap();
+
+        // 128 entries of 128 bytes fill 32 blocks
+        device.reads.set(0);
+        assert_eq!(iter.count(), 2);
+        assert_eq!(device.reads.get(), 32);
+    }
+
+    #[test]
+    fn rejects_small_scratch_buffers() {
+        let device = Disk::new(GPT_DISK);
+        let mut scratch = [0u8; BLOCK_SIZE - 1];
+        assert!(matches!(parse(&device, &mut scratch), Err(PartitionError::ScratchTooSmall)));
+    }
+}
@@ -0,0 +1,136 @@
+use super::{BlockError, BlockRead, BlockWrite};
+use crate::multiboot2::modules::Modules;
+use core::slice::from_raw_parts_mut;
+
+#[derive(Debug)]
+pub enum RamDiskError {
+    InvalidBlockSize,
+    InvalidModuleRange,
+}
+
+/*
+ * A block device backed by a plain byte region (usually a multiboot2 module).
+ * Any trailing bytes that do not fill a whole block are not accessible.
+ */
+pub struct RamDisk<'a> {
+    data: &'a mut [u8],
+    block_size: usize,
+}
+
+impl<'a> RamDisk<'a> {
+    pub fn new(data: &'a mut [u8], block_size: usize) -> Result<Self, RamDiskError> {
+        if block_size == 0 || !block_size.is_power_of_two() {
+            return Err(RamDiskError::InvalidBlockSize);
+        }
+
+        Ok(RamDisk { data, block_size })
+    }
+
+    /*
+     * Safety: The module memory must be mapped at the same virtual address (identity mapped) and nothing else
+     * may access it while the `RamDisk` exists. The bootloader places the modules in memory that the frame allocator
+     * does not know about, so it is up to the caller to make sure it is not handed out while in use.
+     */
+    pub unsafe fn from_module(module: &Modules, block_size: usize) -> Result<RamDisk<'static>, RamDiskError> {
+        let range = module.range().map_err(|_| RamDiskError::InvalidModuleRange)?;
+        let data = from_raw_parts_mut(range.start as *mut u8, range.len());
+        RamDisk::new(data, block_size)
+    }
+
+    // returns the byte range of the block at `lba`, making sure that it is in bounds
+    fn block_range(&self, lba: u64, buf_len: usize) -> Result<core::ops::Range<usize>, BlockError> {
+        if buf_len != self.block_size {
+            return Err(BlockError::InvalidBufferSize);
+        }
+
+        if lba >= self.block_count() {
+            return Err(BlockError::OutOfRange);
+        }
+
+        let start = lba as usize * self.block_size;
+        Ok(start..start + self.block_size)
+    }
+}
+
+impl<'a> BlockRead for RamDisk<'a> {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        (self.data.len() / self.block_size) as u64
+    }
+
+    fn read_block(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        let range = self.block_range(lba, buf.len())?;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+}
+
+impl<'a> BlockWrite for RamDisk<'a> {
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        let range = self.block_range(lba, buf.len())?;
+        self.data[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_blocks() {
+        let mut data = [0u8; 4 * 512];
+        let mut disk = RamDisk::new(&mut data, 512).unwrap();
+        assert_eq!(disk.block_count(), 4);
+
+        let block = [0xabu8; 512];
+        disk.write_block(2, &block).unwrap();
+
+        let mut buf = [0u8; 512];
+        disk.read_block(2, &mut buf).unwrap();
+        assert_eq!(buf, block);
+
+        // the neighbouring blocks are untouched
+        disk.read_block(1, &mut buf).unwrap();
+        assert_eq!(buf, [0u8; 512]);
+        disk.read_block(3, &mut buf).unwrap();
+        assert_eq!(buf, [0u8; 512]);
+
+        assert!(data[2 * 512..3 * 512].iter().all(|&b| b == 0xab));
+    }
+
+    #[test]
+    fn rejects_out_of_range_lbas() {
+        // the trailing 100 bytes don't make up a whole block
+        let mut data = [0u8; 2 * 512 + 100];
+        let mut disk = RamDisk::new(&mut data, 512).unwrap();
+        assert_eq!(disk.block_count(), 2);
+
+        let mut buf = [0u8; 512];
+        assert!(matches!(disk.read_block(2, &mut buf), Err(BlockError::OutOfRange)));
+        assert!(matches!(disk.read_block(u64::MAX, &mut buf), Err(BlockError::OutOfRange)));
+        assert!(matches!(disk.write_block(2, &buf), Err(BlockError::OutOfRange)));
+    }
+
+    #[test]
+    fn rejects_wrong_buffer_sizes() {
+        let mut data = [0u8; 2 * 512];
+        let mut disk = RamDisk::new(&mut data, 512).unwrap();
+
+        let mut small = [0u8; 511];
+        let big = [0u8; 1024];
+        assert!(matches!(disk.read_block(0, &mut small), Err(BlockError::InvalidBufferSize)));
+        assert!(matches!(disk.write_block(0, &big), Err(BlockError::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn rejects_invalid_block_sizes() {
+        let mut data = [0u8; 1024];
+        assert!(matches!(RamDisk::new(&mut data, 0), Err(RamDiskError::InvalidBlockSize)));
+        assert!(matches!(RamDisk::new(&mut data, 500), Err(RamDiskError::InvalidBlockSize)));
+        assert!(RamDisk::new(&mut data, 1024).is_ok());
+    }
+}